@@ -6,6 +6,8 @@
 pub(crate) enum Type<'a> {
     SumType(SumType<'a>),
     ProdType(ProdType<'a>),
+    Alias(Alias<'a>),
+    Extern(Extern<'a>),
 }
 impl<'a> From<SumType<'a>> for Type<'a> {
     fn from(n: SumType) -> Type {
@@ -17,156 +19,367 @@ impl<'a> From<ProdType<'a>> for Type<'a> {
         Type::ProdType(n)
     }
 }
+impl<'a> From<Alias<'a>> for Type<'a> {
+    fn from(n: Alias) -> Type {
+        Type::Alias(n)
+    }
+}
+impl<'a> From<Extern<'a>> for Type<'a> {
+    fn from(n: Extern) -> Type {
+        Type::Extern(n)
+    }
+}
 
 
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub(crate) struct SumType<'a> {
     pub(crate) type_id: TypeId<'a>,
+    pub(crate) params: Vec<Id<'a>>,
     pub(crate) constructors: Vec<Constr<'a>>,
     pub(crate) attrs: Option<Attrs<'a>>,
-    pub(crate) comments: Vec<&'a str>,
+    pub(crate) annotations: Vec<Annotation<'a>>,
+    pub(crate) comments: Vec<Comment<'a>>,
+    pub(crate) span: Span<'a>,
 }
 impl<'a> SumType<'a> {
 
-    pub(crate) fn new(type_id: TypeId<'a>, constructors: Vec<Constr<'a>>, attrs: Option<Attrs<'a>>, comments: Vec<&'a str>) -> Self {
-        SumType{ type_id, constructors, attrs, comments }
+    pub(crate) fn new(type_id: TypeId<'a>, params: Vec<Id<'a>>, constructors: Vec<Constr<'a>>, attrs: Option<Attrs<'a>>, annotations: Vec<Annotation<'a>>, comments: Vec<Comment<'a>>, span: Span<'a>) -> Self {
+        SumType{ type_id, params, constructors, attrs, annotations, comments, span }
     }
 }
 
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub(crate) struct ProdType<'a> {
     pub(crate) type_id: TypeId<'a>,
+    pub(crate) params: Vec<Id<'a>>,
     pub(crate) fields: Vec<Field<'a>>,
-    pub(crate) comments: Vec<&'a str>,
+    pub(crate) attrs: Option<Attrs<'a>>,
+    pub(crate) annotations: Vec<Annotation<'a>>,
+    pub(crate) comments: Vec<Comment<'a>>,
+    pub(crate) span: Span<'a>,
 }
 impl<'a> ProdType<'a> {
 
-    pub(crate) fn new(type_id: TypeId<'a>, fields: Vec<Field<'a>>, comments: Vec<&'a str>) -> Self {
-        ProdType{ type_id, fields, comments }
+    pub(crate) fn new(type_id: TypeId<'a>, params: Vec<Id<'a>>, fields: Vec<Field<'a>>, attrs: Option<Attrs<'a>>, annotations: Vec<Annotation<'a>>, comments: Vec<Comment<'a>>, span: Span<'a>) -> Self {
+        ProdType{ type_id, params, fields, attrs, annotations, comments, span }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) struct Alias<'a> {
+    pub(crate) type_id: TypeId<'a>,
+    pub(crate) target: TypeId<'a>,
+    pub(crate) annotations: Vec<Annotation<'a>>,
+    pub(crate) comments: Vec<Comment<'a>>,
+    pub(crate) span: Span<'a>,
+}
+impl<'a> Alias<'a> {
+
+    pub(crate) fn new(type_id: TypeId<'a>, target: TypeId<'a>, annotations: Vec<Annotation<'a>>, comments: Vec<Comment<'a>>, span: Span<'a>) -> Self {
+        Alias{ type_id, target, annotations, comments, span }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) struct Extern<'a> {
+    pub(crate) type_id: TypeId<'a>,
+    pub(crate) mappings: Vec<Mapping<'a>>,
+    pub(crate) annotations: Vec<Annotation<'a>>,
+    pub(crate) comments: Vec<Comment<'a>>,
+    pub(crate) span: Span<'a>,
+}
+impl<'a> Extern<'a> {
+
+    pub(crate) fn new(type_id: TypeId<'a>, mappings: Vec<Mapping<'a>>, annotations: Vec<Annotation<'a>>, comments: Vec<Comment<'a>>, span: Span<'a>) -> Self {
+        Extern{ type_id, mappings, annotations, comments, span }
     }
 }
 #[derive(PartialEq, Eq, Hash, Debug)]
-pub(crate) enum Field<'a> {
-    Required(Required<'a>),
-    Optional(Optional<'a>),
-    Repeated(Repeated<'a>),
+pub(crate) enum TypeRef<'a> {
+    Named(Named<'a>),
+    Map(Map<'a>),
+    Tuple(Tuple<'a>),
 }
-impl<'a> From<Required<'a>> for Field<'a> {
-    fn from(n: Required) -> Field {
-        Field::Required(n)
+impl<'a> From<Named<'a>> for TypeRef<'a> {
+    fn from(n: Named) -> TypeRef {
+        TypeRef::Named(n)
     }
 }
-impl<'a> From<Optional<'a>> for Field<'a> {
-    fn from(n: Optional) -> Field {
-        Field::Optional(n)
+impl<'a> From<Map<'a>> for TypeRef<'a> {
+    fn from(n: Map) -> TypeRef {
+        TypeRef::Map(n)
     }
 }
-impl<'a> From<Repeated<'a>> for Field<'a> {
-    fn from(n: Repeated) -> Field {
-        Field::Repeated(n)
+impl<'a> From<Tuple<'a>> for TypeRef<'a> {
+    fn from(n: Tuple) -> TypeRef {
+        TypeRef::Tuple(n)
     }
 }
 
 
 #[derive(PartialEq, Eq, Hash, Debug)]
-pub(crate) struct Required<'a> {
+pub(crate) struct Named<'a> {
     pub(crate) type_id: TypeId<'a>,
-    pub(crate) id: Option<Id<'a>>,
+    pub(crate) args: Vec<TypeArg<'a>>,
+    pub(crate) constraint: Option<Constraint<'a>>,
+    pub(crate) arity: &'a str,
 }
-impl<'a> Required<'a> {
+impl<'a> Named<'a> {
 
-    pub(crate) fn new(type_id: TypeId<'a>, id: Option<Id<'a>>) -> Self {
-        Required{ type_id, id }
+    pub(crate) fn new(type_id: TypeId<'a>, args: Vec<TypeArg<'a>>, constraint: Option<Constraint<'a>>, arity: &'a str) -> Self {
+        Named{ type_id, args, constraint, arity }
     }
 }
 
 #[derive(PartialEq, Eq, Hash, Debug)]
-pub(crate) struct Optional<'a> {
-    pub(crate) type_id: TypeId<'a>,
-    pub(crate) id: Option<Id<'a>>,
+pub(crate) struct Map<'a> {
+    pub(crate) key: TypeId<'a>,
+    pub(crate) value: Box<TypeRef<'a>>,
+    pub(crate) arity: &'a str,
 }
-impl<'a> Optional<'a> {
+impl<'a> Map<'a> {
 
-    pub(crate) fn new(type_id: TypeId<'a>, id: Option<Id<'a>>) -> Self {
-        Optional{ type_id, id }
+    pub(crate) fn new(key: TypeId<'a>, value: Box<TypeRef<'a>>, arity: &'a str) -> Self {
+        Map{ key, value, arity }
     }
 }
 
 #[derive(PartialEq, Eq, Hash, Debug)]
-pub(crate) struct Repeated<'a> {
-    pub(crate) type_id: TypeId<'a>,
-    pub(crate) id: Option<Id<'a>>,
+pub(crate) struct Tuple<'a> {
+    pub(crate) fields: Vec<Field<'a>>,
+    pub(crate) arity: &'a str,
 }
-impl<'a> Repeated<'a> {
+impl<'a> Tuple<'a> {
 
-    pub(crate) fn new(type_id: TypeId<'a>, id: Option<Id<'a>>) -> Self {
-        Repeated{ type_id, id }
+    pub(crate) fn new(fields: Vec<Field<'a>>, arity: &'a str) -> Self {
+        Tuple{ fields, arity }
+    }
+}
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) enum Constraint<'a> {
+    Range(Range<'a>),
+    Bound(Bound<'a>),
+    Pattern(Pattern<'a>),
+}
+impl<'a> From<Range<'a>> for Constraint<'a> {
+    fn from(n: Range) -> Constraint {
+        Constraint::Range(n)
+    }
+}
+impl<'a> From<Bound<'a>> for Constraint<'a> {
+    fn from(n: Bound) -> Constraint {
+        Constraint::Bound(n)
+    }
+}
+impl<'a> From<Pattern<'a>> for Constraint<'a> {
+    fn from(n: Pattern) -> Constraint {
+        Constraint::Pattern(n)
+    }
+}
+
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) struct Range<'a> {
+    pub(crate) min: &'a str,
+    pub(crate) max: &'a str,
+}
+impl<'a> Range<'a> {
+
+    pub(crate) fn new(min: &'a str, max: &'a str) -> Self {
+        Range{ min, max }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) struct Bound<'a> {
+    pub(crate) op: &'a str,
+    pub(crate) value: &'a str,
+}
+impl<'a> Bound<'a> {
+
+    pub(crate) fn new(op: &'a str, value: &'a str) -> Self {
+        Bound{ op, value }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) struct Pattern<'a> {
+    pub(crate) regex: &'a str,
+}
+impl<'a> Pattern<'a> {
+
+    pub(crate) fn new(regex: &'a str) -> Self {
+        Pattern{ regex }
     }
 }
 
 
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub(crate) struct Root<'a> {
+    pub(crate) module: Option<Module<'a>>,
+    pub(crate) imports: Vec<Import<'a>>,
     pub(crate) types: Vec<Type<'a>>,
-    pub(crate) comments: Vec<&'a str>,
+    pub(crate) comments: Vec<Comment<'a>>,
 }
 impl<'a> Root<'a> {
 
-    pub(crate) fn new(types: Vec<Type<'a>>, comments: Vec<&'a str>) -> Self {
-        Root{ types, comments }
+    pub(crate) fn new(module: Option<Module<'a>>, imports: Vec<Import<'a>>, types: Vec<Type<'a>>, comments: Vec<Comment<'a>>) -> Self {
+        Root{ module, imports, types, comments }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) struct Module<'a> {
+    pub(crate) id: Id<'a>,
+    pub(crate) version: Option<&'a str>,
+    pub(crate) span: Span<'a>,
+}
+impl<'a> Module<'a> {
+
+    pub(crate) fn new(id: Id<'a>, version: Option<&'a str>, span: Span<'a>) -> Self {
+        Module{ id, version, span }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) struct Import<'a> {
+    pub(crate) path: &'a str,
+    pub(crate) span: Span<'a>,
+}
+impl<'a> Import<'a> {
+
+    pub(crate) fn new(path: &'a str, span: Span<'a>) -> Self {
+        Import{ path, span }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) struct Mapping<'a> {
+    pub(crate) language: Id<'a>,
+    pub(crate) target: &'a str,
+    pub(crate) span: Span<'a>,
+}
+impl<'a> Mapping<'a> {
+
+    pub(crate) fn new(language: Id<'a>, target: &'a str, span: Span<'a>) -> Self {
+        Mapping{ language, target, span }
     }
 }
 
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub(crate) struct Constr<'a> {
     pub(crate) id: ConstrId<'a>,
+    pub(crate) tag: Option<&'a str>,
     pub(crate) fields: Vec<Field<'a>>,
-    pub(crate) comments: Vec<&'a str>,
+    pub(crate) annotations: Vec<Annotation<'a>>,
+    pub(crate) comments: Vec<Comment<'a>>,
+    pub(crate) span: Span<'a>,
 }
 impl<'a> Constr<'a> {
 
-    pub(crate) fn new(id: ConstrId<'a>, fields: Vec<Field<'a>>, comments: Vec<&'a str>) -> Self {
-        Constr{ id, fields, comments }
+    pub(crate) fn new(id: ConstrId<'a>, tag: Option<&'a str>, fields: Vec<Field<'a>>, annotations: Vec<Annotation<'a>>, comments: Vec<Comment<'a>>, span: Span<'a>) -> Self {
+        Constr{ id, tag, fields, annotations, comments, span }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) struct Field<'a> {
+    pub(crate) tag: Option<&'a str>,
+    pub(crate) ty: TypeRef<'a>,
+    pub(crate) id: Option<Id<'a>>,
+    pub(crate) default: Option<&'a str>,
+    pub(crate) annotations: Vec<Annotation<'a>>,
+    pub(crate) comments: Vec<Comment<'a>>,
+    pub(crate) span: Span<'a>,
+}
+impl<'a> Field<'a> {
+
+    pub(crate) fn new(tag: Option<&'a str>, ty: TypeRef<'a>, id: Option<Id<'a>>, default: Option<&'a str>, annotations: Vec<Annotation<'a>>, comments: Vec<Comment<'a>>, span: Span<'a>) -> Self {
+        Field{ tag, ty, id, default, annotations, comments, span }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) struct TypeArg<'a> {
+    pub(crate) type_id: TypeId<'a>,
+    pub(crate) args: Vec<TypeArg<'a>>,
+}
+impl<'a> TypeArg<'a> {
+
+    pub(crate) fn new(type_id: TypeId<'a>, args: Vec<TypeArg<'a>>) -> Self {
+        TypeArg{ type_id, args }
     }
 }
 
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub(crate) struct Attrs<'a> {
     pub(crate) fields: Vec<Field<'a>>,
+    pub(crate) span: Span<'a>,
 }
 impl<'a> Attrs<'a> {
 
-    pub(crate) fn new(fields: Vec<Field<'a>>) -> Self {
-        Attrs{ fields }
+    pub(crate) fn new(fields: Vec<Field<'a>>, span: Span<'a>) -> Self {
+        Attrs{ fields, span }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) struct Annotation<'a> {
+    pub(crate) name: Id<'a>,
+    pub(crate) value: Option<&'a str>,
+    pub(crate) span: Span<'a>,
+}
+impl<'a> Annotation<'a> {
+
+    pub(crate) fn new(name: Id<'a>, value: Option<&'a str>, span: Span<'a>) -> Self {
+        Annotation{ name, value, span }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) struct Comment<'a> {
+    pub(crate) text: &'a str,
+    pub(crate) span: Span<'a>,
+}
+impl<'a> Comment<'a> {
+
+    pub(crate) fn new(text: &'a str, span: Span<'a>) -> Self {
+        Comment{ text, span }
     }
 }
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub(crate) struct TypeId<'a>(pub(crate) &'a str);
 
-impl<'a> ToString for TypeId<'a> {
+impl<'a> std::fmt::Display for TypeId<'a> {
 
-    #[allow(dead_code)]
-    fn to_string(&self) -> String {
-        self.0.to_string()
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub(crate) struct ConstrId<'a>(pub(crate) &'a str);
 
-impl<'a> ToString for ConstrId<'a> {
+impl<'a> std::fmt::Display for ConstrId<'a> {
 
-    #[allow(dead_code)]
-    fn to_string(&self) -> String {
-        self.0.to_string()
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub(crate) struct Id<'a>(pub(crate) &'a str);
 
-impl<'a> ToString for Id<'a> {
+impl<'a> std::fmt::Display for Id<'a> {
+
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub(crate) struct Span<'a>(pub(crate) &'a str);
+
+impl<'a> std::fmt::Display for Span<'a> {
 
-    #[allow(dead_code)]
-    fn to_string(&self) -> String {
-        self.0.to_string()
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
\ No newline at end of file
@@ -0,0 +1,61 @@
+use std::collections::BTreeSet;
+
+/// Primitive types of the classic ASDL specification, and `bool` for flags
+/// with `true` or `false` defaults.
+pub const ASDL_BUILTINS: &[&str] = &["bool", "identifier", "int", "string"];
+
+/// Additional primitive types used by CPython's `Python.asdl`.
+pub const CPYTHON_BUILTINS: &[&str] = &["constant", "bytes", "object"];
+
+/// Registry of leaf types which fields may reference without declaring them.
+///
+/// `Builtins::default()` contains the classic ASDL and CPython primitives.
+/// Projects with their own leaf types can extend it with `insert`/`with`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Builtins {
+    types: BTreeSet<String>,
+}
+
+impl Builtins {
+    /// Creates a registry without any builtin type.
+    pub fn empty() -> Self {
+        Builtins { types: BTreeSet::new() }
+    }
+
+    /// Registers a builtin type, for chaining.
+    pub fn with<S: Into<String>>(mut self, type_id: S) -> Self {
+        self.insert(type_id);
+        self
+    }
+
+    /// Registers a builtin type. Returns `false` if it was already registered.
+    pub fn insert<S: Into<String>>(&mut self, type_id: S) -> bool {
+        self.types.insert(type_id.into())
+    }
+
+    /// Unregisters a builtin type. Returns `false` if it was not registered.
+    pub fn remove(&mut self, type_id: &str) -> bool {
+        self.types.remove(type_id)
+    }
+
+    /// Whether `type_id` names a builtin type.
+    pub fn contains(&self, type_id: &str) -> bool {
+        self.types.contains(type_id)
+    }
+
+    /// The registered name equal to `type_id`, if it is a builtin type.
+    pub fn get(&self, type_id: &str) -> Option<&str> {
+        self.types.get(type_id).map(String::as_str)
+    }
+
+    /// Builtin types in alphabetical order.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.types.iter().map(String::as_str)
+    }
+}
+
+impl Default for Builtins {
+    fn default() -> Self {
+        ASDL_BUILTINS.iter().chain(CPYTHON_BUILTINS).fold(Builtins::empty(), |b, t| b.with(*t))
+    }
+}
@@ -0,0 +1,408 @@
+use std::fmt;
+use std::iter::Peekable;
+
+use nom::Offset;
+
+use crate::ast;
+use crate::error::{AsdlError, Result};
+use crate::model::Asdl;
+use crate::parser;
+
+/// Kind of a token or a node of the concrete syntax tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    Whitespace,
+    /// Line, block and doc comments.
+    Comment,
+    /// Identifiers, integers and the `module`, `version` and `attributes`
+    /// keywords.
+    Ident,
+    String,
+    /// `/regex/` of a constraint.
+    Pattern,
+    Eq,
+    Pipe,
+    Comma,
+    Colon,
+    Question,
+    Star,
+    Plus,
+    Minus,
+    DotDot,
+    At,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LAngle,
+    RAngle,
+    /// Character which does not start any token.
+    Error,
+
+    Root,
+    Import,
+    Module,
+    SumType,
+    ProdType,
+    AliasType,
+    ExternType,
+    Constructor,
+    Attributes,
+    Field,
+    Annotation,
+}
+
+impl SyntaxKind {
+    /// Whitespace and comments.
+    pub fn is_trivia(self) -> bool {
+        self == SyntaxKind::Whitespace || self == SyntaxKind::Comment
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct SyntaxToken {
+    pub kind: SyntaxKind,
+    pub text: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+/// Node of the lossless syntax tree. Its tokens, including whitespace and
+/// comments, hold every byte of the source it was parsed from.
+///
+/// Comments right above a type, a constructor or a field belong to its node,
+/// as do its annotations.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    pub fn parse(src: &str) -> Result<SyntaxNode> {
+        let (root, mut errors) = SyntaxNode::parse_partial(src);
+        if errors.is_empty() {
+            Ok(root)
+        } else {
+            Err(errors.remove(0))
+        }
+    }
+
+    /// Builds a tree for any input. Text which is not part of a successfully
+    /// parsed declaration is kept as tokens of the enclosing node.
+    pub fn parse_partial(src: &str) -> (SyntaxNode, Vec<AsdlError>) {
+        let (root, errors) = parser::parse(src);
+        let mut ranges = vec![];
+        nodes(src, &root, &mut ranges);
+        ranges.sort_by_key(|r| (r.start, std::cmp::Reverse(r.end)));
+        let mut tokens = lex(src).into_iter().peekable();
+        let mut ranges = ranges.into_iter().peekable();
+        let root = build(SyntaxKind::Root, src.len(), &mut tokens, &mut ranges);
+        (root, errors)
+    }
+
+    /// Source text of the node.
+    pub fn text(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn tokens(&self) -> Vec<&SyntaxToken> {
+        let mut tokens = vec![];
+        for child in self.children.iter() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    pub fn child_nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|c| match c {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// Parses the text of the tree into the model.
+    pub fn to_asdl(&self) -> Result<Asdl> {
+        Asdl::parse(&self.text())
+    }
+
+    fn fmt_tree(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        writeln!(f, "{:indent$}{:?}", "", self.kind, indent = indent)?;
+        for child in self.children.iter() {
+            match child {
+                SyntaxElement::Node(node) => node.fmt_tree(f, indent + 2)?,
+                SyntaxElement::Token(token) => {
+                    writeln!(f, "{:indent$}{:?}", "", token, indent = indent + 2)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for token in self.tokens() {
+            f.write_str(&token.text)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_tree(f, 0)
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} {:?}", self.kind, self.text)
+    }
+}
+
+/// Byte range of a node in the source.
+struct NodeRange {
+    kind: SyntaxKind,
+    start: usize,
+    end: usize,
+}
+
+fn build<I, R>(
+    kind: SyntaxKind,
+    end: usize,
+    tokens: &mut Peekable<I>,
+    ranges: &mut Peekable<R>,
+) -> SyntaxNode
+where
+    I: Iterator<Item = (usize, SyntaxToken)>,
+    R: Iterator<Item = NodeRange>,
+{
+    let mut children = vec![];
+    while let Some(&(offset, _)) = tokens.peek() {
+        if offset >= end {
+            break;
+        }
+        match ranges.peek() {
+            Some(range) if range.start <= offset && range.end <= end => {
+                let range = ranges.next().unwrap();
+                children.push(SyntaxElement::Node(build(range.kind, range.end, tokens, ranges)));
+            }
+            _ => children.push(SyntaxElement::Token(tokens.next().unwrap().1)),
+        }
+    }
+    SyntaxNode { kind, children }
+}
+
+fn nodes(src: &str, root: &ast::Root, ranges: &mut Vec<NodeRange>) {
+    let mut ranges = Ranges { src, ranges };
+    for import in root.imports.iter() {
+        ranges.push(SyntaxKind::Import, None, &import.span);
+    }
+    if let Some(module) = &root.module {
+        ranges.push(SyntaxKind::Module, None, &module.span);
+    }
+    for ty in root.types.iter() {
+        match ty {
+            ast::Type::SumType(ty) => {
+                let start = ranges.annotations(&ty.annotations);
+                let start = ranges.leading(&ty.comments, &ty.span).or(start);
+                ranges.push(SyntaxKind::SumType, start, &ty.span);
+                for constr in ty.constructors.iter() {
+                    let start = ranges.annotations(&constr.annotations);
+                    let start = ranges.leading(&constr.comments, &constr.span).or(start);
+                    ranges.push(SyntaxKind::Constructor, start, &constr.span);
+                    ranges.fields(&constr.fields);
+                }
+                if let Some(attrs) = &ty.attrs {
+                    ranges.push(SyntaxKind::Attributes, None, &attrs.span);
+                    ranges.fields(&attrs.fields);
+                }
+            }
+            ast::Type::ProdType(ty) => {
+                let start = ranges.annotations(&ty.annotations);
+                let start = ranges.leading(&ty.comments, &ty.span).or(start);
+                ranges.push(SyntaxKind::ProdType, start, &ty.span);
+                ranges.fields(&ty.fields);
+                if let Some(attrs) = &ty.attrs {
+                    ranges.push(SyntaxKind::Attributes, None, &attrs.span);
+                    ranges.fields(&attrs.fields);
+                }
+            }
+            ast::Type::Alias(ty) => {
+                let start = ranges.annotations(&ty.annotations);
+                let start = ranges.leading(&ty.comments, &ty.span).or(start);
+                ranges.push(SyntaxKind::AliasType, start, &ty.span);
+            }
+            ast::Type::Extern(ty) => {
+                let start = ranges.annotations(&ty.annotations);
+                let start = ranges.leading(&ty.comments, &ty.span).or(start);
+                ranges.push(SyntaxKind::ExternType, start, &ty.span);
+            }
+        }
+    }
+}
+
+struct Ranges<'s, 'r> {
+    src: &'s str,
+    ranges: &'r mut Vec<NodeRange>,
+}
+
+impl Ranges<'_, '_> {
+    /// Adds a node which ends with `span` and starts with `start`, or with
+    /// `span` if there is nothing before it.
+    fn push(&mut self, kind: SyntaxKind, start: Option<&ast::Span>, span: &ast::Span) {
+        let start = self.src.offset(start.unwrap_or(span).0);
+        self.ranges.push(NodeRange { kind, start, end: self.src.offset(span.0) + span.0.len() });
+    }
+
+    /// Adds annotation nodes and returns the span of the first one.
+    fn annotations<'a, 'b>(
+        &mut self,
+        annotations: &'b [ast::Annotation<'a>],
+    ) -> Option<&'b ast::Span<'a>> {
+        for annotation in annotations {
+            self.push(SyntaxKind::Annotation, None, &annotation.span);
+        }
+        annotations.first().map(|a| &a.span)
+    }
+
+    /// Span of the first comment above the declaration at `span`. Comments
+    /// trailing it are not part of its node.
+    fn leading<'a, 'b>(
+        &self,
+        comments: &'b [ast::Comment<'a>],
+        span: &ast::Span,
+    ) -> Option<&'b ast::Span<'a>> {
+        let start = self.src.offset(span.0);
+        comments.first().map(|c| &c.span).filter(|c| self.src.offset(c.0) < start)
+    }
+
+    fn fields(&mut self, fields: &[ast::Field]) {
+        for field in fields {
+            let start = self.annotations(&field.annotations);
+            let start = self.leading(&field.comments, &field.span).or(start);
+            self.push(SyntaxKind::Field, start, &field.span);
+            let mut ty = &field.ty;
+            while let ast::TypeRef::Map(map) = ty {
+                ty = &map.value;
+            }
+            if let ast::TypeRef::Tuple(tuple) = ty {
+                self.fields(&tuple.fields);
+            }
+        }
+    }
+}
+
+/// Splits the source into tokens paired with their offsets.
+fn lex(src: &str) -> Vec<(usize, SyntaxToken)> {
+    let mut tokens = vec![];
+    let mut offset = 0;
+    while offset < src.len() {
+        let rest = &src[offset..];
+        let c = rest.chars().next().unwrap();
+        let (kind, len) = if c.is_whitespace() {
+            (SyntaxKind::Whitespace, prefix_len(rest, char::is_whitespace))
+        } else if rest.starts_with("//") || rest.starts_with("--") {
+            let line = rest.find(['\r', '\n']).map_or(rest, |n| &rest[..n]);
+            (SyntaxKind::Comment, line.trim_end().len())
+        } else if rest.starts_with("/*") {
+            (SyntaxKind::Comment, rest.find("*/").map_or(rest.len(), |n| n + 2))
+        } else if c == '"' {
+            (SyntaxKind::String, rest[1..].find('"').map_or(rest.len(), |n| n + 2))
+        } else if c == '/' {
+            (SyntaxKind::Pattern, pattern_len(rest))
+        } else if rest.starts_with("..") {
+            (SyntaxKind::DotDot, 2)
+        } else if c.is_alphanumeric() || c == '_' {
+            (SyntaxKind::Ident, prefix_len(rest, |c| c.is_alphanumeric() || c == '_'))
+        } else {
+            let kind = match c {
+                '=' => SyntaxKind::Eq,
+                '|' => SyntaxKind::Pipe,
+                ',' => SyntaxKind::Comma,
+                ':' => SyntaxKind::Colon,
+                '?' => SyntaxKind::Question,
+                '*' => SyntaxKind::Star,
+                '+' => SyntaxKind::Plus,
+                '-' => SyntaxKind::Minus,
+                '@' => SyntaxKind::At,
+                '(' => SyntaxKind::LParen,
+                ')' => SyntaxKind::RParen,
+                '{' => SyntaxKind::LBrace,
+                '}' => SyntaxKind::RBrace,
+                '<' => SyntaxKind::LAngle,
+                '>' => SyntaxKind::RAngle,
+                _ => SyntaxKind::Error,
+            };
+            (kind, c.len_utf8())
+        };
+        tokens.push((offset, SyntaxToken { kind, text: rest[..len].to_string() }));
+        offset += len;
+    }
+    tokens
+}
+
+/// Length of a `/regex/` up to the closing slash or the end of the line.
+fn pattern_len(text: &str) -> usize {
+    let mut chars = text.char_indices().skip(1);
+    while let Some((n, c)) = chars.next() {
+        match c {
+            '/' => return n + 1,
+            '\\' => {
+                chars.next();
+            }
+            '\r' | '\n' => return n,
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+fn prefix_len(text: &str, f: impl Fn(char) -> bool) -> usize {
+    text.find(|c| !f(c)).unwrap_or(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_debug_snapshot_matches;
+
+    #[test]
+    fn lossless_round_trip() {
+        let asdl = "// root\r\n\nimport  \"a.asdl\"\n module M version \"1\" {\n\t// stm  \n  stm = A(int a, // b\n \
+                    int( >=0 )* b = None, int(-9..9) c=-1)|@a( x )B(string(/a\\/b/) s)\n    attributes ( int  line )\n  expr=(stm? /* e */) -- x\n}\n// tail";
+        let (cst, errors) = SyntaxNode::parse_partial(asdl);
+        assert!(errors.is_empty());
+        assert_eq!(cst.text(), asdl);
+        assert_eq!(cst.to_asdl().unwrap(), Asdl::parse(asdl).unwrap());
+
+        let broken = "stm = A(int a | B €\n\nexpr = Name(  ";
+        let (cst, errors) = SyntaxNode::parse_partial(broken);
+        assert_eq!(errors.len(), 3);
+        assert_eq!(cst.text(), broken);
+    }
+
+    #[test]
+    fn cst_nodes() {
+        let asdl = "stm = // Compound\n  Compound(stm s) | Pass\n  attributes (int line)\n\
+             @boxed\nid = (@rename(\"s\") string) attributes (int line)\n// name\nname = identifier\nextern span(rust = \"Span\")";
+        let cst = SyntaxNode::parse(asdl).unwrap();
+        assert_debug_snapshot_matches!("cst_nodes", cst);
+    }
+
+    #[test]
+    fn cst_tuple_fields() {
+        let asdl = "dict = ((expr key, {identifier: (int, int)}) items)";
+        let cst = SyntaxNode::parse(asdl).unwrap();
+        assert_eq!(cst.text(), asdl);
+        assert_debug_snapshot_matches!("cst_tuple_fields", cst);
+    }
+}
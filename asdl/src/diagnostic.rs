@@ -0,0 +1,334 @@
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use crate::error::AsdlError;
+use crate::span::Span;
+use crate::validation::ValidationError;
+
+/// Message attached to a region of the source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new<S: Into<String>>(span: Span, message: S) -> Self {
+        Label { span, message: message.into() }
+    }
+}
+
+/// Error prepared for rendering: a message, the primary location,
+/// secondary locations and free-form notes. `file` is the file the labels
+/// point into, if it is known. Errors about a whole file have no primary
+/// label.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub message: String,
+    pub primary: Option<Label>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub file: Option<PathBuf>,
+}
+
+impl Diagnostic {
+    pub fn new<S: Into<String>>(message: S, primary: Label) -> Self {
+        Diagnostic { primary: Some(primary), ..Diagnostic::without_location(message) }
+    }
+
+    /// Diagnostic which does not point into the source.
+    pub fn without_location<S: Into<String>>(message: S) -> Self {
+        Diagnostic {
+            message: message.into(),
+            primary: None,
+            secondary: vec![],
+            notes: vec![],
+            file: None,
+        }
+    }
+
+    pub fn with_secondary(mut self, label: Label) -> Self {
+        self.secondary.push(label);
+        self
+    }
+
+    pub fn with_note<S: Into<String>>(mut self, note: S) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+}
+
+impl From<&AsdlError> for Diagnostic {
+    fn from(err: &AsdlError) -> Self {
+        let label = if err.expected.is_empty() {
+            String::new()
+        } else {
+            format!("expected {}", err.expected.join(" or "))
+        };
+        let mut diagnostic = match err.span {
+            Some(span) => Diagnostic::new(err.kind.message(), Label::new(span, label)),
+            None => Diagnostic::without_location(err.kind.message()),
+        };
+        diagnostic.notes = err.notes.clone();
+        diagnostic.file = err.file.clone();
+        diagnostic
+    }
+}
+
+impl From<&ValidationError> for Diagnostic {
+    fn from(err: &ValidationError) -> Self {
+        let message = err.to_string();
+        match err {
+            ValidationError::UndefinedType { span, .. } => {
+                Diagnostic::new(message, Label::new(*span, "undefined type"))
+            }
+            ValidationError::WrongTypeArgCount { span, .. } => {
+                Diagnostic::new(message, Label::new(*span, "wrong number of type arguments"))
+            }
+            ValidationError::UndefinedTarget { span, .. } => {
+                Diagnostic::new(message, Label::new(*span, "undefined type"))
+            }
+            ValidationError::CyclicAlias { span, .. } => {
+                Diagnostic::new(message, Label::new(*span, "alias cycle"))
+            }
+            ValidationError::InvalidConstraint { span, .. } => {
+                Diagnostic::new(message, Label::new(*span, "constraint does not fit the type"))
+            }
+            ValidationError::InvalidPattern { span, .. } => {
+                Diagnostic::new(message, Label::new(*span, "invalid pattern"))
+            }
+            ValidationError::EmptyRange { span, .. } => {
+                Diagnostic::new(message, Label::new(*span, "empty range"))
+            }
+            ValidationError::InvalidDefault { span, .. } => {
+                Diagnostic::new(message, Label::new(*span, "invalid default value"))
+            }
+            ValidationError::DuplicateType { span, first, .. } => {
+                Diagnostic::new(message, Label::new(*span, "type redefined here"))
+                    .with_secondary(Label::new(*first, "type first defined here"))
+            }
+            ValidationError::DuplicateConstructor { span, first, .. } => {
+                Diagnostic::new(message, Label::new(*span, "constructor redefined here"))
+                    .with_secondary(Label::new(*first, "constructor first defined here"))
+            }
+            ValidationError::DuplicateMapping { span, first, .. } => {
+                Diagnostic::new(message, Label::new(*span, "language mapped again here"))
+                    .with_secondary(Label::new(*first, "language first mapped here"))
+            }
+            ValidationError::DuplicateTag { span, first, .. } => {
+                Diagnostic::new(message, Label::new(*span, "tag reused here"))
+                    .with_secondary(Label::new(*first, "tag first used here"))
+            }
+            ValidationError::DuplicateField { span, first, .. } => {
+                Diagnostic::new(message, Label::new(*span, "field redefined here"))
+                    .with_secondary(Label::new(*first, "field first defined here"))
+            }
+            ValidationError::InfiniteInstantiation { span, .. } => {
+                Diagnostic::new(message, Label::new(*span, "type arguments grow here"))
+            }
+            ValidationError::InstanceClash { span, first, .. } => {
+                Diagnostic::new(message, Label::new(*span, "instantiated here"))
+                    .with_secondary(Label::new(*first, "name first used here"))
+            }
+            ValidationError::ReservedTag { span, .. } => {
+                Diagnostic::new(message, Label::new(*span, "reserved tag"))
+            }
+        }
+    }
+}
+
+const RED: &str = "\u{1b}[1;31m";
+const BLUE: &str = "\u{1b}[1;34m";
+const BOLD: &str = "\u{1b}[1m";
+const RESET: &str = "\u{1b}[0m";
+
+/// Renders diagnostics in the style of compiler error messages:
+///
+/// ```text
+/// error: Missing '=' after type id
+///  --> schema.asdl:4:8
+///   |
+/// 4 | notType&
+///   |        ^ expected '='
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Renderer {
+    color: bool,
+}
+
+impl Renderer {
+    pub fn plain() -> Self {
+        Renderer { color: false }
+    }
+
+    /// Renderer which highlights the output with ANSI escape sequences.
+    pub fn colored() -> Self {
+        Renderer { color: true }
+    }
+
+    /// Renders `diagnostic` against the source `src` of `file_name`. Labels
+    /// without a location, line or column 0, get no snippet. Without a
+    /// primary label, or if it has no location, only the message, the file
+    /// and the notes are shown.
+    pub fn render(&self, file_name: &str, src: &str, diagnostic: &Diagnostic) -> String {
+        let primary = diagnostic.primary.as_ref().filter(|l| has_location(&l.span));
+        let mut labels: Vec<(&Label, bool)> = primary.map(|l| (l, true)).into_iter().collect();
+        labels.extend(diagnostic.secondary.iter().map(|l| (l, false)));
+        labels.retain(|(l, _)| has_location(&l.span));
+        labels.sort_by_key(|(l, _)| l.span.start.offset);
+        let lines: Vec<&str> = src.lines().collect();
+        let max_line = labels.iter().map(|(l, _)| l.span.start.line).max().unwrap_or(1);
+        let gutter = " ".repeat(max_line.to_string().len());
+
+        let mut out = String::new();
+        writeln!(out, "{}: {}", self.paint(RED, "error"), self.paint(BOLD, &diagnostic.message))
+            .unwrap();
+        let primary = match primary {
+            Some(primary) => primary,
+            None => {
+                writeln!(out, "{}{} {}", gutter, self.paint(BLUE, "-->"), file_name).unwrap();
+                for note in diagnostic.notes.iter() {
+                    writeln!(out, "{} {} note: {}", gutter, self.paint(BLUE, "="), note).unwrap();
+                }
+                return out;
+            }
+        };
+        let start = primary.span.start;
+        writeln!(
+            out,
+            "{}{} {}:{}:{}",
+            gutter,
+            self.paint(BLUE, "-->"),
+            file_name,
+            start.line,
+            start.column
+        )
+        .unwrap();
+        writeln!(out, "{} {}", gutter, self.paint(BLUE, "|")).unwrap();
+        let mut prev_line = None;
+        for (label, is_primary) in labels {
+            let line_no = label.span.start.line;
+            let line = lines.get(line_no - 1).cloned().unwrap_or("");
+            if prev_line != Some(line_no) {
+                if prev_line.is_some_and(|p| p + 1 < line_no) {
+                    writeln!(out, "{}", self.paint(BLUE, "...")).unwrap();
+                }
+                let number = format!("{:>width$} |", line_no, width = gutter.len());
+                writeln!(out, "{} {}", self.paint(BLUE, &number), expand_tabs(line)).unwrap();
+            }
+            prev_line = Some(line_no);
+
+            let column = label.span.start.column - 1;
+            let prefix: String = line.chars().take(column).collect();
+            let end_column = if label.span.end.line == line_no {
+                label.span.end.column.saturating_sub(1)
+            } else {
+                line.chars().count()
+            };
+            let underlined: String =
+                line.chars().skip(column).take(end_column.saturating_sub(column)).collect();
+            let width = expand_tabs(&underlined).chars().count().max(1);
+            let (mark, color) = if is_primary { ("^", RED) } else { ("-", BLUE) };
+            let marks = format!("{} {}", mark.repeat(width), label.message);
+            writeln!(
+                out,
+                "{} {} {}{}",
+                gutter,
+                self.paint(BLUE, "|"),
+                " ".repeat(expand_tabs(&prefix).chars().count()),
+                self.paint(color, marks.trim_end())
+            )
+            .unwrap();
+        }
+        for note in diagnostic.notes.iter() {
+            writeln!(out, "{} {} note: {}", gutter, self.paint(BLUE, "="), note).unwrap();
+        }
+        out
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Whether `span` points into the source. Locations are 1-based, errors
+/// which do not come from a place in the source have line and column 0.
+fn has_location(span: &Span) -> bool {
+    span.start.line > 0 && span.start.column > 0
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', "    ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Asdl;
+
+    #[test]
+    fn render_parse_error() {
+        let asdl = "// comment\n\nnotType&";
+        let err = Asdl::parse(asdl).unwrap_err();
+        let text = Renderer::plain().render("test.asdl", asdl, &(&err).into());
+        assert_eq!(
+            text,
+            "\
+error: Missing '=' after type id
+ --> test.asdl:3:8
+  |
+3 | notType&
+  |        ^ expected '='
+  = note: Expected Product or Sum type declaration
+"
+        );
+    }
+
+    #[test]
+    fn render_secondary_labels() {
+        let asdl = "stm = Single\n\n\nexpr = Name\nstm = (expr)";
+        let errors = Asdl::parse(asdl).unwrap().validate().unwrap_err();
+        let text = Renderer::plain().render("test.asdl", asdl, &(&errors[0]).into());
+        assert_eq!(
+            text,
+            "\
+error: Type `stm` is defined more than once
+ --> test.asdl:5:1
+  |
+1 | stm = Single
+  | ------------ type first defined here
+...
+5 | stm = (expr)
+  | ^^^^^^^^^^^^ type redefined here
+"
+        );
+        let colored = Renderer::colored().render("test.asdl", asdl, &(&errors[0]).into());
+        assert!(colored.starts_with("\u{1b}[1;31merror\u{1b}[0m: "));
+    }
+
+    #[test]
+    fn render_without_location() {
+        let diagnostic = Diagnostic::new("Cannot read file", Label::new(Span::default(), ""))
+            .with_secondary(Label::new(Span::default(), "also here"))
+            .with_note("schema.asdl: No such file or directory");
+        let text = Renderer::plain().render("schema.asdl", "", &diagnostic);
+        assert_eq!(
+            text,
+            "\
+error: Cannot read file
+ --> schema.asdl
+  = note: schema.asdl: No such file or directory
+"
+        );
+
+        let asdl = "stm = Single\nstm = Pass";
+        let diagnostic = Diagnostic::new("Duplicate", Label::new(Span::default(), "here"))
+            .with_secondary(Label::new(Span::default(), "first"));
+        let text = Renderer::plain().render("test.asdl", asdl, &diagnostic);
+        assert_eq!(text, "error: Duplicate\n --> test.asdl\n");
+    }
+}
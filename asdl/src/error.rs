@@ -0,0 +1,170 @@
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::span::Span;
+
+pub type Result<T> = std::result::Result<T, AsdlError>;
+
+/// Kind of a syntax error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The input does not match any of the expected alternatives.
+    UnexpectedToken,
+    /// The input ended where more was expected.
+    UnexpectedEof,
+    /// A type id does not start with a lowercase character.
+    BadTypeIdCase,
+    /// A constructor id does not start with an uppercase character.
+    BadConstructorIdCase,
+    /// A field or module name does not start with an alphanumeric character.
+    BadIdentifier,
+    /// A comment does not start with `//`, `--` or `/*`.
+    BadComment,
+    /// A type id is not followed by `=`.
+    MissingEquals,
+    /// A field type is followed by something other than an arity, a name, `,` or `)`.
+    BadArity,
+    /// A map key type is not followed by `:`.
+    MissingColon,
+    /// A map type is not closed with `}`.
+    UnterminatedMap,
+    /// Type parameters of a declaration are not closed with `>`.
+    UnterminatedTypeParams,
+    /// Type arguments of a field type are not closed with `>`.
+    UnterminatedTypeArgs,
+    /// The `=` after a constructor id is not followed by a tag number, or a
+    /// tag does not fit into a `u32`.
+    BadTag,
+    /// The tag at the start of a field is not followed by `:`.
+    MissingTagColon,
+    /// The `=` after a field is not followed by a literal.
+    BadDefault,
+    /// The parentheses after a field type hold neither a range, a bound nor
+    /// a pattern.
+    BadConstraint,
+    /// A strict bound such as `>9223372036854775807` leaves no `i64` value.
+    EmptyBound,
+    /// A constraint is not closed with `)`.
+    UnterminatedConstraint,
+    /// A pattern is not closed with `/` on the same line.
+    UnterminatedPattern,
+    /// A target language mapping is not of the form `language = "name"`.
+    BadMapping,
+    /// Target language mappings of an extern type are not closed with `)`.
+    UnterminatedMappings,
+    /// A field list is not closed with `)`.
+    UnterminatedFieldList,
+    /// A module header is not followed by `{`.
+    MissingModuleBody,
+    /// A module is not closed with `}`.
+    UnterminatedModule,
+    /// A string literal is not closed with `"`.
+    UnterminatedString,
+    /// A block comment is not closed with `*/`.
+    UnterminatedComment,
+    /// Arguments of an annotation are not closed with `)`.
+    UnterminatedAnnotation,
+    /// Neither a product nor a sum type declaration could be parsed.
+    ExpectedTypeDeclaration,
+    /// A schema file or a file it imports could not be read.
+    UnreadableFile,
+    /// A file imports itself, directly or through other files.
+    ImportCycle,
+    /// A line of a tag lock file is not a `path = tag` entry.
+    BadLockEntry,
+}
+
+impl ErrorKind {
+    pub fn message(self) -> &'static str {
+        match self {
+            ErrorKind::UnexpectedToken => "Unexpected token",
+            ErrorKind::UnexpectedEof => "Unexpected end of input",
+            ErrorKind::BadTypeIdCase => "Type Id should start with lowercase character",
+            ErrorKind::BadConstructorIdCase => {
+                "Constructor Id should start with uppercase character"
+            }
+            ErrorKind::BadIdentifier => "Id should start with alpha character",
+            ErrorKind::BadComment => "Comment should start with '//', '--' or '/*'",
+            ErrorKind::MissingEquals => "Missing '=' after type id",
+            ErrorKind::BadArity => "Unexpected character after field type",
+            ErrorKind::MissingColon => "Missing ':' after map key type",
+            ErrorKind::UnterminatedMap => "Unterminated map type",
+            ErrorKind::UnterminatedTypeParams => "Unterminated type parameter list",
+            ErrorKind::UnterminatedTypeArgs => "Unterminated type argument list",
+            ErrorKind::BadTag => "Invalid tag",
+            ErrorKind::MissingTagColon => "Missing ':' after field tag",
+            ErrorKind::BadDefault => "Invalid default value",
+            ErrorKind::BadConstraint => "Invalid constraint",
+            ErrorKind::EmptyBound => "Bound admits no value",
+            ErrorKind::UnterminatedConstraint => "Unterminated constraint",
+            ErrorKind::UnterminatedPattern => "Unterminated pattern",
+            ErrorKind::BadMapping => "Invalid extern mapping",
+            ErrorKind::UnterminatedMappings => "Unterminated extern mapping list",
+            ErrorKind::UnterminatedFieldList => "Unterminated field list",
+            ErrorKind::MissingModuleBody => "Missing module body",
+            ErrorKind::UnterminatedModule => "Unterminated module",
+            ErrorKind::UnterminatedString => "Unterminated string",
+            ErrorKind::UnterminatedComment => "Unterminated block comment",
+            ErrorKind::UnterminatedAnnotation => "Unterminated annotation arguments",
+            ErrorKind::ExpectedTypeDeclaration => "Expected Product or Sum type declaration",
+            ErrorKind::UnreadableFile => "Cannot read file",
+            ErrorKind::ImportCycle => "Import cycle",
+            ErrorKind::BadLockEntry => "Invalid lock file entry",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+/// Syntax error found while parsing a schema.
+///
+/// `span` points at the offending input, it is empty at the end of input
+/// and missing for errors about a whole file, such as a file which cannot
+/// be read. `expected` lists the alternatives the parser would have accepted there
+/// and `notes` describe what was being parsed, innermost first. `file` is
+/// the file containing the error when the schema was loaded from files.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AsdlError {
+    pub kind: ErrorKind,
+    pub span: Option<Span>,
+    pub expected: Vec<String>,
+    pub notes: Vec<String>,
+    pub file: Option<PathBuf>,
+}
+
+impl AsdlError {
+    pub fn new(kind: ErrorKind, span: Span) -> Self {
+        AsdlError { kind, span: Some(span), expected: vec![], notes: vec![], file: None }
+    }
+
+    /// Error about the whole of `file`, without a location in it.
+    pub fn in_file(kind: ErrorKind, file: PathBuf) -> Self {
+        AsdlError { kind, span: None, expected: vec![], notes: vec![], file: Some(file) }
+    }
+}
+
+impl fmt::Display for AsdlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.file, self.span) {
+            (Some(file), Some(span)) => write!(f, "{}:{}: ", file.display(), span)?,
+            (Some(file), None) => write!(f, "{}: ", file.display())?,
+            (None, Some(span)) => write!(f, "{}: ", span)?,
+            (None, None) => {}
+        }
+        write!(f, "{}", self.kind)?;
+        if !self.expected.is_empty() {
+            write!(f, ", expected {}", self.expected.join(" or "))?;
+        }
+        for note in self.notes.iter() {
+            write!(f, "\nnote: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for AsdlError {}
@@ -1,10 +1,28 @@
 mod ast;
+mod cst;
 mod parser;
+mod printer;
 mod model;
 mod model_impl;
+mod builtins;
+mod diagnostic;
+mod error;
+mod loader;
+mod lock;
+mod span;
+mod validation;
+mod monomorphize;
 mod util;
 
 pub use model::*;
+pub use builtins::*;
+pub use cst::*;
+pub use diagnostic::*;
+pub use error::*;
+pub use lock::*;
+pub use printer::*;
+pub use span::*;
+pub use validation::*;
 
 #[cfg(test)]
 mod tests {
@@ -35,9 +53,10 @@ mod tests {
             // prodType comment line 2
             prodType = (noFields? f, stm s1)
             ";
-        let (_, root) = parser::parse(&asdl).unwrap();
+        let (root, errors) = parser::parse(asdl);
+        assert_eq!(errors, vec![]);
         assert_debug_snapshot_matches!("simple_successful_test_syntax", root);
-        let model = Asdl::new(&root);
+        let model = Asdl::new(asdl, &root, Builtins::default(), 0);
         assert_debug_snapshot_matches!("simple_successful_test_model", model)
     }
 
@@ -49,9 +68,293 @@ mod tests {
                   attributes(prodType?)
             prodType = (stm s1)
             ";
-        let (_, root) = parser::parse(&asdl).unwrap();
+        let (root, errors) = parser::parse(asdl);
+        assert_eq!(errors, vec![]);
         assert_debug_snapshot_matches!("attributes_syntax", root);
-        let model = Asdl::new(&root);
+        let model = Asdl::new(asdl, &root, Builtins::default(), 0);
         assert_debug_snapshot_matches!("attributes_model", model)
     }
+
+    #[test]
+    fn module() {
+        let asdl = r#"
+            module Python version "$Revision$"
+            {
+                mod = Module(stmt* body) | Expression(expr body)
+                stmt = Pass
+                expr = Name(identifier id)
+            }
+            "#;
+        let model = Asdl::parse(asdl).unwrap();
+        let module = model.module.unwrap();
+        assert_eq!(module.id, "Python");
+        assert_eq!(module.version, Some("$Revision$".to_string()));
+        assert_eq!(model.types.len(), 3);
+    }
+
+    #[test]
+    fn builtins() {
+        let asdl = r"
+            expr = Name(identifier id, span) | Const(constant value, int? kind)
+            span = (int start, int end)
+            ";
+        let model = Asdl::parse(asdl).unwrap();
+        let span = model.get_type_by_name("span").unwrap();
+        let index = model.index();
+        assert_eq!(index.resolve("identifier"), Some(TypeRef::Builtin("identifier")));
+        assert_eq!(index.resolve("span"), Some(span.into()));
+        assert_eq!(index.resolve("str"), None);
+        assert!(!model.is_builtin("str"));
+
+        let model = Asdl::parse_with_builtins(asdl, Builtins::default().with("str")).unwrap();
+        assert!(model.is_builtin("str"));
+        assert!(model.is_builtin("int"));
+        assert!(!model.is_builtin("span"));
+    }
+
+    #[test]
+    fn type_index() {
+        let asdl = r"
+            expr = Name(identifier id, loc) | Num(big_int value)
+            loc = (int start, int end)
+            extern big_int
+            name = identifier
+            ";
+        let model = Asdl::parse(asdl).unwrap();
+        let index = model.index();
+        assert_eq!(index.get("loc").map(Type::id), Some("loc"));
+        assert_eq!(index.get("int"), None);
+        let kinds: Vec<_> = match index.get("expr") {
+            Some(Type::SumType(sty)) => sty
+                .constructors
+                .iter()
+                .flat_map(|c| c.fields.iter())
+                .map(|f| index.field_type(f).map(|t| (t.id(), t.kind())))
+                .collect(),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            kinds,
+            vec![
+                Some(("identifier", "builtin")),
+                Some(("loc", "prod")),
+                Some(("big_int", "extern"))
+            ]
+        );
+        assert_eq!(index.resolve("name").map(|t| t.kind()), Some("alias"));
+        assert_eq!(index.resolve("expr"), model.get_type_by_name("expr").map(TypeRef::from));
+        assert_eq!(index.resolve("str"), None);
+    }
+
+    #[test]
+    fn arity() {
+        let asdl = "dict = (expr?* keys, expr+ values, stm** blocks, int id)";
+        let model = Asdl::parse(asdl).unwrap();
+        let fields = match &model.types[0] {
+            Type::ProdType(pty) => &pty.fields,
+            _ => unreachable!(),
+        };
+        let arities: Vec<_> = fields.iter().map(|f| &f.arity).collect();
+        let required = || Box::new(Arity::Required);
+        assert_eq!(
+            arities,
+            vec![
+                &Arity::Repeated(Box::new(Arity::Optional(required()))),
+                &Arity::NonEmpty(required()),
+                &Arity::Repeated(Box::new(Arity::Repeated(required()))),
+                &Arity::Required,
+            ]
+        );
+        assert!(fields[1].arity.is_sequence());
+        assert_eq!(fields[0].arity.inner(), Some(&Arity::Optional(required())));
+        assert_eq!(model.to_string(), format!("{}\n", asdl));
+    }
+
+    #[test]
+    fn map_arity() {
+        let asdl = "call = ({identifier: expr} keywords, {string: {int: expr?}*}? nested)";
+        let model = Asdl::parse(asdl).unwrap();
+        let fields = match &model.types[0] {
+            Type::ProdType(pty) => &pty.fields,
+            _ => unreachable!(),
+        };
+        let map = |key: &str, value| Arity::Map { key: key.into(), value: Box::new(value) };
+        assert_eq!(fields[0].type_id, "expr");
+        assert_eq!(fields[0].arity, map("identifier", Arity::Required));
+        let optional = Arity::Optional(Box::new(Arity::Required));
+        let inner = Arity::Repeated(Box::new(map("int", optional)));
+        assert_eq!(fields[1].arity, Arity::Optional(Box::new(map("string", inner))));
+        assert_eq!(model.to_string(), format!("{}\n", asdl));
+    }
+
+    #[test]
+    fn tuple_fields() {
+        let asdl =
+            "stm = Dict((expr, expr)* items) | Call({identifier: (expr value, int)} args, (int))";
+        let model = Asdl::parse(asdl).unwrap();
+        let constructors = match &model.types[0] {
+            Type::SumType(sty) => &sty.constructors,
+            _ => unreachable!(),
+        };
+        let items = &constructors[0].fields[0];
+        assert_eq!(items.type_id, "");
+        assert_eq!(items.arity, Arity::Repeated(Box::new(Arity::Required)));
+        let elements = items.tuple.as_ref().unwrap();
+        let ids: Vec<_> = elements.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, vec!["expr", "expr1"]);
+        let args = &constructors[1].fields;
+        assert_eq!(args[0].tuple.as_ref().unwrap()[1].id, "int");
+        assert_eq!(args[1].id, "tuple");
+        assert_eq!(model.to_string(), format!("{}\n", asdl));
+    }
+
+    #[test]
+    fn alias_types() {
+        let asdl = "/// label\nlabel = string\n\nstm = Goto(label)";
+        let model = Asdl::parse(asdl).unwrap();
+        match &model.types[0] {
+            Type::AliasType(aty) => {
+                assert_eq!((aty.id.as_str(), aty.target.as_str()), ("label", "string"));
+                assert_eq!(aty.comments[0].text, "label");
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(model.to_string(), format!("{}\n", asdl));
+    }
+
+    #[test]
+    fn generic_types() {
+        let asdl = "stm = Expr(spanned<located<expr>>* values)\n\
+                    spanned<T> = (T node, int start, int end)\n\
+                    located<T, U> = Loc(T value, {identifier: U} ctx)";
+        let model = Asdl::parse(asdl).unwrap();
+        assert_eq!(model.types[1].params(), &["T".to_string()]);
+        assert_eq!(model.types[2].params(), &["T".to_string(), "U".to_string()]);
+        let field = match &model.types[0] {
+            Type::SumType(sty) => &sty.constructors[0].fields[0],
+            _ => unreachable!(),
+        };
+        let expr = TypeArg { type_id: "expr".into(), args: vec![] };
+        let located = TypeArg { type_id: "located".into(), args: vec![expr] };
+        assert_eq!((field.type_id.as_str(), &field.args), ("spanned", &vec![located]));
+        assert_eq!(model.to_string(), format!("{}\n", asdl));
+    }
+
+    #[test]
+    fn field_defaults() {
+        let asdl =
+            "def = (int n = -1, bool b = false, string s = \"\", expr? e = None, ctx c = Load)";
+        let model = Asdl::parse(asdl).unwrap();
+        let fields = match &model.types[0] {
+            Type::ProdType(pty) => &pty.fields,
+            _ => unreachable!(),
+        };
+        let defaults: Vec<_> = fields.iter().map(|f| f.default.clone().unwrap()).collect();
+        assert_eq!(
+            defaults,
+            vec![
+                Literal::Int(-1),
+                Literal::Bool(false),
+                Literal::String("".into()),
+                Literal::None,
+                Literal::Constructor("Load".into()),
+            ]
+        );
+        assert_eq!(model.to_string(), format!("{}\n", asdl));
+    }
+
+    #[test]
+    fn field_constraints() {
+        let asdl = r"op = (int(0..255) code, string(/[a-z_]+\//) name, int(>0)* depths)";
+        let model = Asdl::parse(asdl).unwrap();
+        let fields = match &model.types[0] {
+            Type::ProdType(pty) => &pty.fields,
+            _ => unreachable!(),
+        };
+        let constraints: Vec<_> = fields.iter().map(|f| f.constraint.clone().unwrap()).collect();
+        assert_eq!(
+            constraints,
+            vec![
+                Constraint::Range { min: Some(0), max: Some(255) },
+                Constraint::Pattern(r"[a-z_]+\/".into()),
+                Constraint::Range { min: Some(1), max: None },
+            ]
+        );
+        let printed = r"op = (int(0..255) code, string(/[a-z_]+\//) name, int(>=1)* depths)";
+        assert_eq!(model.to_string(), format!("{}\n", printed));
+    }
+
+    #[test]
+    fn extern_types() {
+        let asdl =
+            "/// source range\nextern span(rust = \"text_size::TextRange\", python = \"Span\")\n\n\
+                    extern big_int\nexpr = Num(big_int value, span)";
+        let model = Asdl::parse(asdl).unwrap();
+        match model.index().resolve("span") {
+            Some(TypeRef::Extern(ety)) => {
+                assert_eq!(ety.mapping("rust"), Some("text_size::TextRange"));
+                assert_eq!(ety.mapping("python"), Some("Span"));
+                assert_eq!(ety.mapping("c"), None);
+                assert_eq!(ety.comments[0].text, "source range");
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(model.validate(), Ok(()));
+        assert_eq!(model.to_string(), format!("{}\n", asdl));
+    }
+
+    #[test]
+    fn tags() {
+        let asdl = "stm = Compound = 3 (1: stm s1, 2: stm* s2) | Pass = 7\nname = (1: identifier)";
+        let model = Asdl::parse(asdl).unwrap();
+        let constructors = match &model.types[0] {
+            Type::SumType(sty) => &sty.constructors,
+            _ => unreachable!(),
+        };
+        let tags: Vec<_> = constructors.iter().map(|c| c.tag).collect();
+        assert_eq!(tags, vec![Some(3), Some(7)]);
+        let tags: Vec<_> = constructors[0].fields.iter().map(|f| f.tag).collect();
+        assert_eq!(tags, vec![Some(1), Some(2)]);
+        assert_eq!(model.to_string(), format!("{}\n", asdl));
+    }
+
+    #[test]
+    fn spans() {
+        let asdl =
+            "// root\n\n// stm\nstm = Compound(stm s1,\n    stm* s2) | Single\nprodType = (stm)";
+        let model = Asdl::parse(asdl).unwrap();
+        let stm = match &model.types[0] {
+            Type::SumType(sty) => sty,
+            _ => unreachable!(),
+        };
+        let compound = &stm.constructors[0];
+        assert_eq!(&asdl[stm.span.range()], "stm = Compound(stm s1,\n    stm* s2) | Single");
+        assert_eq!(&asdl[stm.comments[0].span.range()], "// stm");
+        assert_eq!(&asdl[compound.span.range()], "Compound(stm s1,\n    stm* s2)");
+        assert_eq!(&asdl[stm.constructors[1].span.range()], "Single");
+        let s2 = compound.fields[1].span;
+        assert_eq!(&asdl[s2.range()], "stm* s2");
+        assert_eq!((s2.start.line, s2.start.column, s2.end.line, s2.end.column), (5, 5, 5, 12));
+        assert_eq!(&asdl[model.types[1].span().range()], "prodType = (stm)");
+    }
+
+    #[test]
+    fn parse_error() {
+        let err = Asdl::parse("stm = Compound(stm s1\nexpr = Name").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnterminatedFieldList);
+        let span = err.span.unwrap();
+        assert_eq!((span.start.line, span.start.column), (2, 1));
+        assert_eq!(&span.range(), &(22..23));
+        assert_eq!(err.expected, vec!["')'"]);
+
+        let (model, errors) = Asdl::parse_partial("stm = Compound(stm s1\nexpr = Name\nmod = ");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].kind, ErrorKind::BadConstructorIdCase);
+        let ids: Vec<_> = model.types.iter().map(Type::id).collect();
+        assert_eq!(ids, vec!["stm", "expr", "mod"]);
+
+        let err = Asdl::parse("module M version \"1").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnterminatedString);
+        assert_eq!(err.span.map(|s| s.range()), Some(19..19));
+    }
 }
@@ -0,0 +1,203 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::builtins::Builtins;
+use crate::error::{AsdlError, ErrorKind, Result};
+use crate::model::Asdl;
+use crate::parser;
+use crate::span::Span;
+
+impl Asdl {
+    /// Loads the schema in `path` together with all files it imports. Import
+    /// paths are relative to the importing file. Types of every file are
+    /// merged into one model, the `file` of their spans indexes `files`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Asdl> {
+        let (asdl, mut errors) = Asdl::load_partial(path);
+        if errors.is_empty() {
+            Ok(asdl)
+        } else {
+            Err(errors.remove(0))
+        }
+    }
+
+    /// Loads the schema in `path` without stopping at the first error.
+    /// Returns everything that could be loaded together with all errors.
+    pub fn load_partial<P: AsRef<Path>>(path: P) -> (Asdl, Vec<AsdlError>) {
+        let mut loader = Loader::default();
+        let mut asdl = loader.load(path.as_ref(), None).unwrap_or_else(|| Asdl {
+            module: None,
+            imports: vec![],
+            types: vec![],
+            comments: vec![],
+            builtins: Builtins::default(),
+            files: vec![],
+        });
+        asdl.files = loader.files;
+        (asdl, loader.errors)
+    }
+}
+
+#[derive(Default)]
+struct Loader {
+    files: Vec<PathBuf>,
+    canonical_files: Vec<PathBuf>,
+    /// Indexes of the files whose imports are being loaded.
+    stack: Vec<usize>,
+    errors: Vec<AsdlError>,
+}
+
+impl Loader {
+    /// Loads `path` and the files it imports. `import` locates the import
+    /// declaration, it is `None` for the root file. Returns `None` for files
+    /// which could not be read or were already loaded.
+    fn load(&mut self, path: &Path, import: Option<(usize, Span)>) -> Option<Asdl> {
+        let read = fs::canonicalize(path).and_then(|c| fs::read_to_string(&c).map(|s| (c, s)));
+        let (canonical, src) = match read {
+            Ok(read) => read,
+            Err(err) => {
+                let note = format!("{}: {}", path.display(), err);
+                self.error(ErrorKind::UnreadableFile, path, import, vec![note]);
+                return None;
+            }
+        };
+        if let Some(file) = self.canonical_files.iter().position(|f| *f == canonical) {
+            if let Some(n) = self.stack.iter().position(|f| *f == file) {
+                let mut chain: Vec<_> = self.stack[n..].iter().map(|f| &self.files[*f]).collect();
+                chain.push(&self.files[file]);
+                let notes = chain
+                    .windows(2)
+                    .map(|w| format!("`{}` imports `{}`", w[0].display(), w[1].display()))
+                    .collect();
+                self.error(ErrorKind::ImportCycle, path, import, notes);
+            }
+            return None;
+        }
+
+        let file = self.files.len();
+        self.files.push(path.to_path_buf());
+        self.canonical_files.push(canonical);
+        let (root, errors) = parser::parse(&src);
+        for mut err in errors {
+            if let Some(span) = &mut err.span {
+                span.file = file;
+            }
+            err.file = Some(path.to_path_buf());
+            self.errors.push(err);
+        }
+        let mut asdl = Asdl::new(&src, &root, Builtins::default(), file);
+
+        self.stack.push(file);
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let imports: Vec<_> = asdl.imports.iter().map(|i| (dir.join(&i.path), i.span)).collect();
+        for (imported, span) in imports {
+            if let Some(imported) = self.load(&imported, Some((file, span))) {
+                asdl.types.extend(imported.types);
+            }
+        }
+        self.stack.pop();
+        Some(asdl)
+    }
+
+    fn error(
+        &mut self,
+        kind: ErrorKind,
+        path: &Path,
+        import: Option<(usize, Span)>,
+        notes: Vec<String>,
+    ) {
+        let mut err = match import {
+            Some((file, span)) => {
+                AsdlError { file: Some(self.files[file].clone()), ..AsdlError::new(kind, span) }
+            }
+            None => AsdlError::in_file(kind, path.to_path_buf()),
+        };
+        err.notes = notes;
+        self.errors.push(err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Renderer;
+    use crate::model::Type;
+
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("asdl-loader-{}-{}", name, std::process::id()));
+        for (path, src) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, src).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn load_imports() {
+        let dir = write_files(
+            "imports",
+            &[
+                (
+                    "root.asdl",
+                    "import \"ir/stm.asdl\"\nimport \"ir/expr.asdl\"\n\nmod = (stm* body)",
+                ),
+                ("ir/stm.asdl", "import \"expr.asdl\"\nstm = Expr(expr) | Pass"),
+                ("ir/expr.asdl", "expr = Name(identifier id)"),
+            ],
+        );
+        let asdl = Asdl::load(dir.join("root.asdl")).unwrap();
+        assert!(asdl.validate().is_ok());
+        let types: Vec<_> = asdl
+            .types
+            .iter()
+            .map(|t| (t.id(), asdl.file(t).unwrap().strip_prefix(&dir).unwrap().to_path_buf()))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("mod", PathBuf::from("root.asdl")),
+                ("stm", PathBuf::from("ir/stm.asdl")),
+                ("expr", PathBuf::from("ir/expr.asdl")),
+            ]
+        );
+        assert_eq!(asdl.imports.len(), 2);
+        match &asdl.types[2] {
+            Type::SumType(expr) => assert_eq!(expr.constructors[0].span.file, 2),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn load_errors() {
+        let dir = write_files(
+            "errors",
+            &[
+                ("a.asdl", "import \"b.asdl\"\nimport \"missing.asdl\"\na = (b)"),
+                ("b.asdl", "import \"a.asdl\"\nb = B(a) | C(int"),
+            ],
+        );
+        let (asdl, errors) = Asdl::load_partial(dir.join("a.asdl"));
+        assert_eq!(asdl.types.len(), 2);
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|e| {
+                let file = e.file.as_ref().unwrap().strip_prefix(&dir).unwrap().display();
+                (format!("{}:{}: {}", file, e.span.unwrap(), e.kind), e.notes.len())
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                ("b.asdl:2:14: Unterminated field list".to_string(), 0),
+                ("b.asdl:1:1: Import cycle".to_string(), 2),
+                ("a.asdl:2:1: Cannot read file".to_string(), 1),
+            ]
+        );
+        let err = Asdl::load(dir.join("none.asdl")).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnreadableFile);
+        assert_eq!(err.span, None);
+        assert_eq!(err.file, Some(dir.join("none.asdl")));
+        let text = Renderer::plain().render("none.asdl", "", &(&err).into());
+        assert!(text.starts_with("error: Cannot read file\n --> none.asdl\n  = note: "));
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::{AsdlError, ErrorKind, Result};
+use crate::model::*;
+use crate::span::{SourceMap, Span};
+use crate::validation::ValidationError;
+
+const HEADER: &str = "# Tags assigned by asdl. Keep this file under version control.";
+
+/// Tags of constructors and fields recorded in a lock file such as
+/// `schema.asdl.lock`, so that they stay stable when the schema changes.
+///
+/// Entries are keyed by path: `stm.Compound` for a constructor,
+/// `stm.Compound.s1` for its field and `stm.attributes.lineno` for an
+/// attribute. Tags of deleted items are kept, marked `deleted`, so that they
+/// are never handed out again. So are tags replaced by one written in the
+/// schema.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct TagLock {
+    pub entries: BTreeMap<String, LockEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct LockEntry {
+    /// `None` for items which are no longer in the schema.
+    pub tag: Option<u32>,
+    /// Earlier tags of the item, oldest first.
+    pub deleted: Vec<u32>,
+}
+
+impl LockEntry {
+    /// The current tag and the deleted ones.
+    fn tags(&self) -> impl Iterator<Item = u32> + '_ {
+        self.tag.iter().chain(self.deleted.iter()).copied()
+    }
+}
+
+impl TagLock {
+    /// Path of the lock file of `schema`, `schema.asdl.lock` for
+    /// `schema.asdl`.
+    pub fn path<P: AsRef<Path>>(schema: P) -> PathBuf {
+        let mut path = schema.as_ref().as_os_str().to_owned();
+        path.push(".lock");
+        path.into()
+    }
+
+    /// Reads the lock file at `path`. A missing file is an empty lock.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<TagLock> {
+        let path = path.as_ref();
+        let res = match fs::read_to_string(path) {
+            Ok(text) => TagLock::parse(&text),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(TagLock::default()),
+            Err(err) => {
+                let mut e = AsdlError::in_file(ErrorKind::UnreadableFile, path.to_path_buf());
+                e.notes.push(format!("{}: {}", path.display(), err));
+                Err(e)
+            }
+        };
+        res.map_err(|e| AsdlError { file: Some(path.to_path_buf()), ..e })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Parses the text of a lock file: a `path = tag` line per item and a
+    /// `path = tag deleted` line per deleted tag. Empty lines and lines
+    /// starting with `#` are skipped.
+    pub fn parse(text: &str) -> Result<TagLock> {
+        let sm = SourceMap::new(text, 0);
+        let mut entries = BTreeMap::new();
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            match entry(trimmed) {
+                Some((path, tag, deleted)) => {
+                    let entry: &mut LockEntry = entries.entry(path.to_string()).or_default();
+                    if deleted {
+                        entry.deleted.push(tag);
+                    } else {
+                        entry.tag = Some(tag);
+                    }
+                }
+                None => {
+                    let mut err = AsdlError::new(ErrorKind::BadLockEntry, sm.span(trimmed));
+                    err.expected.push("'path = tag'".to_string());
+                    return Err(err);
+                }
+            }
+        }
+        Ok(TagLock { entries })
+    }
+}
+
+/// Splits a `path = tag` line into the path, the tag and whether it is
+/// marked `deleted`.
+fn entry(line: &str) -> Option<(&str, u32, bool)> {
+    let (path, rest) = line.split_once('=')?;
+    let path = path.trim();
+    let mut words = rest.split_whitespace();
+    let tag = words.next()?.parse().ok()?;
+    let deleted = match words.next() {
+        None => false,
+        Some("deleted") => true,
+        Some(_) => return None,
+    };
+    if path.is_empty() || path.contains(char::is_whitespace) || words.next().is_some() {
+        return None;
+    }
+    Some((path, tag, deleted))
+}
+
+impl fmt::Display for TagLock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for (path, entry) in self.entries.iter() {
+            for tag in entry.deleted.iter() {
+                writeln!(f, "{} = {} deleted", path, tag)?;
+            }
+            if let Some(tag) = entry.tag {
+                writeln!(f, "{} = {}", path, tag)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Asdl {
+    /// Gives every constructor and field without a tag the one recorded in
+    /// `lock` or, for new items, one more than the largest tag used or
+    /// reserved among the items it must differ from. Tags written in the
+    /// schema take precedence over the lock.
+    ///
+    /// Returns the updated lock. Items of `lock` which are no longer in the
+    /// schema are kept as deleted, as are locked tags replaced by one written
+    /// in the schema. An item which comes back gets its last tag again. A tag
+    /// written in the schema which the lock holds for another item, current
+    /// or deleted, is an error.
+    pub fn assign_tags(
+        &mut self,
+        lock: &TagLock,
+    ) -> std::result::Result<TagLock, Vec<ValidationError>> {
+        let mut slots = vec![];
+        for ty in self.types.iter_mut() {
+            type_slots(ty, &mut slots);
+        }
+        let mut errors = vec![];
+        let mut entries = BTreeMap::new();
+        for slot in slots.iter_mut() {
+            let locked = lock.entries.get(&slot.path);
+            let tag = match (*slot.tag, locked) {
+                (Some(tag), _) => {
+                    if let Some(other) = slot.reserved_by(lock, tag) {
+                        errors.push(ValidationError::ReservedTag {
+                            tag,
+                            path: slot.path.clone(),
+                            other: other.clone(),
+                            span: slot.span,
+                        });
+                    }
+                    tag
+                }
+                (None, Some(entry)) => match entry.tag.or_else(|| entry.deleted.last().copied()) {
+                    Some(tag) => tag,
+                    None => continue,
+                },
+                (None, None) => continue,
+            };
+            *slot.tag = Some(tag);
+            let mut entry = locked.cloned().unwrap_or_default();
+            entry.deleted.extend(entry.tag.filter(|old| *old != tag));
+            entry.deleted.retain(|old| *old != tag);
+            entry.tag = Some(tag);
+            entries.insert(slot.path.clone(), entry);
+        }
+        for slot in slots.iter_mut().filter(|s| s.tag.is_none()) {
+            let old = lock.entries.iter().filter(|(path, _)| slot.in_group(path));
+            let new = entries.iter().filter(|(path, _)| slot.in_group(path));
+            let tag =
+                old.chain(new).flat_map(|(_, entry)| entry.tags()).max().map_or(1, |max| max + 1);
+            *slot.tag = Some(tag);
+            entries.insert(slot.path.clone(), LockEntry { tag: Some(tag), deleted: vec![] });
+        }
+        for (path, entry) in lock.entries.iter() {
+            entries.entry(path.clone()).or_insert_with(|| {
+                let mut deleted = entry.deleted.clone();
+                deleted.extend(entry.tag);
+                LockEntry { tag: None, deleted }
+            });
+        }
+        if errors.is_empty() {
+            Ok(TagLock { entries })
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Tag of a constructor or field. `group` lists the parent paths of the
+/// items whose tags it must differ from, its own parent included.
+struct Slot<'a> {
+    path: String,
+    group: Vec<String>,
+    tag: &'a mut Option<u32>,
+    span: Span,
+}
+
+impl Slot<'_> {
+    fn in_group(&self, path: &str) -> bool {
+        self.group.iter().any(|g| Some(g.as_str()) == parent(path))
+    }
+
+    /// Path of another item of the group to which `lock` gives `tag`.
+    fn reserved_by<'l>(&self, lock: &'l TagLock, tag: u32) -> Option<&'l String> {
+        let mut others = lock.entries.iter().filter(|(path, _)| **path != self.path);
+        others
+            .find(|(path, entry)| self.in_group(path) && entry.tags().any(|t| t == tag))
+            .map(|(path, _)| path)
+    }
+}
+
+fn parent(path: &str) -> Option<&str> {
+    path.rsplit_once('.').map(|(parent, _)| parent)
+}
+
+/// Collects the tags of `ty`. Constructor fields share their numbers with
+/// the attributes of the type.
+fn type_slots<'a>(ty: &'a mut Type, slots: &mut Vec<Slot<'a>>) {
+    match ty {
+        Type::SumType(SumType { id, constructors, attributes, .. }) => {
+            let attrs = format!("{}.attributes", id);
+            let mut attrs_group: Vec<_> =
+                constructors.iter().map(|c| format!("{}.{}", id, c.id)).collect();
+            attrs_group.push(attrs.clone());
+            for c in constructors.iter_mut() {
+                let path = format!("{}.{}", id, c.id);
+                let group = vec![path.clone(), attrs.clone()];
+                field_slots(&path, &mut c.fields, &group, slots);
+                slots.push(Slot { path, group: vec![id.clone()], tag: &mut c.tag, span: c.span });
+            }
+            field_slots(&attrs, attributes, &attrs_group, slots);
+        }
+        Type::ProdType(ProdType { id, fields, attributes, .. }) => {
+            let attrs = format!("{}.attributes", id);
+            let group = vec![id.clone(), attrs.clone()];
+            field_slots(id, fields, &group, slots);
+            field_slots(&attrs, attributes, &group, slots);
+        }
+        Type::AliasType(_) | Type::ExternType(_) => {}
+    }
+}
+
+/// Collects the tags of `fields` of `parent`. Elements of a tuple are
+/// numbered on their own.
+fn field_slots<'a>(
+    parent: &str,
+    fields: &'a mut [Field],
+    group: &[String],
+    slots: &mut Vec<Slot<'a>>,
+) {
+    for f in fields.iter_mut() {
+        let path = format!("{}.{}", parent, f.id);
+        if let Some(elements) = &mut f.tuple {
+            field_slots(&path, elements, std::slice::from_ref(&path), slots);
+        }
+        slots.push(Slot { path, group: group.to_vec(), tag: &mut f.tag, span: f.span });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(asdl: &Asdl) -> Vec<String> {
+        let mut tags = vec![];
+        let mut slots = vec![];
+        let mut asdl = asdl.clone();
+        for ty in asdl.types.iter_mut() {
+            type_slots(ty, &mut slots);
+        }
+        for slot in slots {
+            tags.push(format!("{} = {}", slot.path, slot.tag.map_or(0, |t| t)));
+        }
+        tags
+    }
+
+    #[test]
+    fn assign_tags() {
+        let asdl = r"
+            stm = Compound(stm s1, stm* s2) | Pass = 5 | Single(1: stm) attributes (int line)
+            pair = ((int, int) p)
+            ";
+        let mut model = Asdl::parse(asdl).unwrap();
+        let lock = model.assign_tags(&TagLock::default()).unwrap();
+        assert_eq!(
+            tags(&model),
+            vec![
+                "stm.Compound.s1 = 1",
+                "stm.Compound.s2 = 2",
+                "stm.Compound = 6",
+                "stm.Pass = 5",
+                "stm.Single.stm = 1",
+                "stm.Single = 7",
+                "stm.attributes.line = 3",
+                "pair.p.int = 1",
+                "pair.p.int1 = 2",
+                "pair.p = 1",
+            ]
+        );
+        assert_eq!(model.validate(), Ok(()));
+        assert_eq!(TagLock::parse(&lock.to_string()), Ok(lock.clone()));
+
+        let asdl = r"
+            stm = Single(stm, int depth) | Compound(stm s1, stm* s2) | Loop
+                attributes (int line)
+            ";
+        let mut model = Asdl::parse(asdl).unwrap();
+        let lock = model.assign_tags(&lock).unwrap();
+        assert_eq!(
+            tags(&model),
+            vec![
+                "stm.Single.stm = 1",
+                "stm.Single.depth = 4",
+                "stm.Single = 7",
+                "stm.Compound.s1 = 1",
+                "stm.Compound.s2 = 2",
+                "stm.Compound = 6",
+                "stm.Loop = 8",
+                "stm.attributes.line = 3",
+            ]
+        );
+        assert_eq!(
+            lock.to_string(),
+            "\
+# Tags assigned by asdl. Keep this file under version control.
+pair.p = 1 deleted
+pair.p.int = 1 deleted
+pair.p.int1 = 2 deleted
+stm.Compound = 6
+stm.Compound.s1 = 1
+stm.Compound.s2 = 2
+stm.Loop = 8
+stm.Pass = 5 deleted
+stm.Single = 7
+stm.Single.depth = 4
+stm.Single.stm = 1
+stm.attributes.line = 3
+"
+        );
+    }
+
+    #[test]
+    fn keep_replaced_tags() {
+        let mut model = Asdl::parse("stm = Compound | Pass").unwrap();
+        let lock = model.assign_tags(&TagLock::default()).unwrap();
+        let mut model = Asdl::parse("stm = Compound = 5 | Pass | Loop").unwrap();
+        let lock = model.assign_tags(&lock).unwrap();
+        assert_eq!(tags(&model), vec!["stm.Compound = 5", "stm.Pass = 2", "stm.Loop = 6"]);
+        assert_eq!(
+            lock.to_string(),
+            "\
+# Tags assigned by asdl. Keep this file under version control.
+stm.Compound = 1 deleted
+stm.Compound = 5
+stm.Loop = 6
+stm.Pass = 2
+"
+        );
+        assert_eq!(TagLock::parse(&lock.to_string()), Ok(lock.clone()));
+
+        let mut model = Asdl::parse("stm = Compound | Loop | Break").unwrap();
+        let lock = model.assign_tags(&lock).unwrap();
+        assert_eq!(tags(&model), vec!["stm.Compound = 5", "stm.Loop = 6", "stm.Break = 7"]);
+        let mut model = Asdl::parse("stm = Compound | Pass | Break = 1").unwrap();
+        let errors = model.assign_tags(&lock).unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec!["Tag `1` of `stm.Break` is reserved for `stm.Compound` by the lock file"]
+        );
+        match &model.types[0] {
+            Type::SumType(sty) => assert_eq!(errors[0].span(), sty.constructors[2].span),
+            _ => unreachable!(),
+        }
+        let mut model = Asdl::parse("stm = Compound | Pass = 6").unwrap();
+        let errors = model.assign_tags(&lock).unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Tag `6` of `stm.Pass` is reserved for `stm.Loop` by the lock file"
+        );
+
+        let mut model = Asdl::parse("stm = Compound = 1 | Pass").unwrap();
+        let lock = model.assign_tags(&lock).unwrap();
+        assert_eq!(tags(&model), vec!["stm.Compound = 1", "stm.Pass = 2"]);
+        assert_eq!(
+            lock.to_string(),
+            "\
+# Tags assigned by asdl. Keep this file under version control.
+stm.Break = 7 deleted
+stm.Compound = 5 deleted
+stm.Compound = 1
+stm.Loop = 6 deleted
+stm.Pass = 2
+"
+        );
+    }
+
+    #[test]
+    fn parse_lock_errors() {
+        let err = TagLock::parse("# header\nstm.Pass = 1\n  stm.Single = x\n").unwrap_err();
+        assert_eq!(err.to_string(), "3:3: Invalid lock file entry, expected 'path = tag'");
+        assert!(TagLock::parse("stm.Pass = 1 removed").is_err());
+        assert_eq!(TagLock::path("dir/schema.asdl"), PathBuf::from("dir/schema.asdl.lock"));
+        assert_eq!(TagLock::load("no/such/schema.asdl.lock"), Ok(TagLock::default()));
+        let dir = std::env::temp_dir();
+        let err = TagLock::load(&dir).unwrap_err();
+        assert_eq!((err.kind, err.span, err.file), (ErrorKind::UnreadableFile, None, Some(dir)));
+    }
+}
@@ -1,63 +1,218 @@
-use std::error::Error;
-use std::fmt;
-use crate::{parser, ast};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-pub type Result<T> = std::result::Result<T, AsdlError>;
+use crate::parser;
+use crate::builtins::Builtins;
+use crate::error::{AsdlError, Result};
+use crate::span::Span;
 
-#[derive(Debug)]
-pub struct AsdlError {
-    details: String,
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Asdl {
+    pub module: Option<Module>,
+    pub imports: Vec<Import>,
+    pub types: Vec<Type>,
+    /// Comments at the start of the schema and, marked trailing, at its end.
+    /// Comments at the start must be followed by an empty line, otherwise
+    /// they belong to the first declaration.
+    pub comments: Vec<Comment>,
+    pub builtins: Builtins,
+    /// Files the schema was loaded from, the root file goes first. Empty if
+    /// the schema was parsed from a string.
+    pub files: Vec<PathBuf>,
 }
 
-impl fmt::Display for AsdlError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.details)
+impl Asdl {
+    pub fn parse(asdl: &str) -> Result<Asdl> {
+        Asdl::parse_with_builtins(asdl, Builtins::default())
+    }
+
+    pub fn parse_with_builtins(asdl: &str, builtins: Builtins) -> Result<Asdl> {
+        let (root, mut errors) = parser::parse(asdl);
+        if errors.is_empty() {
+            Ok(Asdl::new(asdl, &root, builtins, 0))
+        } else {
+            Err(errors.remove(0))
+        }
+    }
+
+    /// Parses `asdl` without stopping at the first syntax error. Returns the
+    /// declarations which could be parsed together with all errors found.
+    pub fn parse_partial(asdl: &str) -> (Asdl, Vec<AsdlError>) {
+        let (root, errors) = parser::parse(asdl);
+        (Asdl::new(asdl, &root, Builtins::default(), 0), errors)
+    }
+
+    /// Finds a declared type by name. Use `index` for repeated lookups.
+    pub fn get_type_by_name(&self, name: &str) -> Option<&Type> {
+        self.types.iter().find(|t| t.id() == name)
+    }
+
+    /// Indexes the types of the schema by name, to resolve type references.
+    pub fn index(&self) -> TypeIndex<'_> {
+        TypeIndex::new(self)
+    }
+
+    pub fn is_builtin(&self, type_id: &str) -> bool {
+        self.builtins.contains(type_id) && self.get_type_by_name(type_id).is_none()
+    }
+
+    /// File the type was loaded from.
+    pub fn file(&self, ty: &Type) -> Option<&Path> {
+        self.files.get(ty.span().file).map(PathBuf::as_path)
     }
 }
 
-impl Error for AsdlError {
-    fn description(&self) -> &str {
-        &self.details
+/// Types of a schema by name, for lookups in constant time. It borrows the
+/// schema so it cannot go stale.
+#[derive(Debug, Clone)]
+pub struct TypeIndex<'a> {
+    types: HashMap<&'a str, &'a Type>,
+    builtins: &'a Builtins,
+}
+
+impl<'a> TypeIndex<'a> {
+    pub fn new(asdl: &'a Asdl) -> Self {
+        let mut types = HashMap::with_capacity(asdl.types.len());
+        for ty in asdl.types.iter() {
+            types.entry(ty.id()).or_insert(ty);
+        }
+        TypeIndex { types, builtins: &asdl.builtins }
+    }
+
+    /// Finds a declared type by name. If several types have the name, the
+    /// first one is found.
+    pub fn get(&self, name: &str) -> Option<&'a Type> {
+        self.types.get(name).copied()
+    }
+
+    /// Resolves a type reference of a field. Declared types shadow builtins.
+    pub fn resolve(&self, type_id: &str) -> Option<TypeRef<'a>> {
+        match self.get(type_id) {
+            Some(ty) => Some(ty.into()),
+            None => self.builtins.get(type_id).map(TypeRef::Builtin),
+        }
+    }
+
+    /// Resolves the type of `field`. `None` for inline tuples and undefined
+    /// types.
+    pub fn field_type(&self, field: &Field) -> Option<TypeRef<'a>> {
+        self.resolve(&field.type_id)
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub struct Asdl {
-    pub types: Vec<Type>,
-    pub comments: Vec<String>,
+/// Target of a field's type reference. Type parameters are not resolved,
+/// they are only known within their declaration.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum TypeRef<'a> {
+    Builtin(&'a str),
+    Sum(&'a SumType),
+    Prod(&'a ProdType),
+    Alias(&'a AliasType),
+    Extern(&'a ExternType),
 }
 
-impl Asdl {
-    pub fn parse(asdl: &str) -> Result<Asdl> {
-        ast(asdl).map(|a| Asdl::new(&a))
+impl<'a> TypeRef<'a> {
+    pub fn id(&self) -> &'a str {
+        match self {
+            TypeRef::Builtin(id) => id,
+            TypeRef::Sum(sty) => &sty.id,
+            TypeRef::Prod(pty) => &pty.id,
+            TypeRef::Alias(aty) => &aty.id,
+            TypeRef::Extern(ety) => &ety.id,
+        }
     }
 
-    pub fn get_type_by_name(&self, name: &str) -> Option<&Type> {
-        self.types.iter().find(|t| t.id() == name)
+    /// Type parameters, empty for all but generic sum and product types.
+    pub fn params(&self) -> &'a [String] {
+        match self {
+            TypeRef::Sum(sty) => &sty.params,
+            TypeRef::Prod(pty) => &pty.params,
+            TypeRef::Builtin(_) | TypeRef::Alias(_) | TypeRef::Extern(_) => &[],
+        }
+    }
+
+    /// `builtin`, `sum`, `prod`, `alias` or `extern`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TypeRef::Builtin(_) => "builtin",
+            TypeRef::Sum(_) => "sum",
+            TypeRef::Prod(_) => "prod",
+            TypeRef::Alias(_) => "alias",
+            TypeRef::Extern(_) => "extern",
+        }
+    }
+}
+
+impl<'a> From<&'a Type> for TypeRef<'a> {
+    fn from(ty: &'a Type) -> TypeRef<'a> {
+        match ty {
+            Type::SumType(sty) => TypeRef::Sum(sty),
+            Type::ProdType(pty) => TypeRef::Prod(pty),
+            Type::AliasType(aty) => TypeRef::Alias(aty),
+            Type::ExternType(ety) => TypeRef::Extern(ety),
+        }
     }
 }
 
-use nom::Err;
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Module {
+    pub id: String,
+    pub version: Option<String>,
+    /// Comments before the closing `}`.
+    pub comments: Vec<Comment>,
+    pub span: Span,
+}
 
-fn ast(asdl: &str) -> Result<ast::Root> {
-    parser::parse(asdl).map(|r| r.1).map_err(|e| match e {
-        Err::Incomplete(_) => AsdlError { details: "Incomplete input".into() },
-        Err::Error(e) => AsdlError { details: parser::convert_error(asdl, e) },
-        Err::Failure(_) => AsdlError { details: "Failure".into() },
-    })
+/// `import "path"` declaration. The path is relative to the importing file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Import {
+    pub path: String,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Type {
     SumType(SumType),
     ProdType(ProdType),
+    AliasType(AliasType),
+    ExternType(ExternType),
 }
 
 impl Type {
-    fn id(&self) -> String {
+    pub fn id(&self) -> &str {
+        match self {
+            Type::SumType(sty) => &sty.id,
+            Type::ProdType(pty) => &pty.id,
+            Type::AliasType(aty) => &aty.id,
+            Type::ExternType(ety) => &ety.id,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Type::SumType(sty) => sty.span,
+            Type::ProdType(pty) => pty.span,
+            Type::AliasType(aty) => aty.span,
+            Type::ExternType(ety) => ety.span,
+        }
+    }
+
+    /// Type parameters, empty for aliases, extern types and types which are
+    /// not generic.
+    pub fn params(&self) -> &[String] {
         match self {
-            Type::SumType(sty) => sty.id.clone(),
-            Type::ProdType(pty) => pty.id.clone(),
+            Type::SumType(sty) => &sty.params,
+            Type::ProdType(pty) => &pty.params,
+            Type::AliasType(_) | Type::ExternType(_) => &[],
+        }
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        match self {
+            Type::SumType(sty) => &sty.annotations,
+            Type::ProdType(pty) => &pty.annotations,
+            Type::AliasType(aty) => &aty.annotations,
+            Type::ExternType(ety) => &ety.annotations,
         }
     }
 }
@@ -72,39 +227,215 @@ impl From<ProdType> for Type {
         Type::ProdType(n)
     }
 }
+impl From<AliasType> for Type {
+    fn from(n: AliasType) -> Type {
+        Type::AliasType(n)
+    }
+}
+impl From<ExternType> for Type {
+    fn from(n: ExternType) -> Type {
+        Type::ExternType(n)
+    }
+}
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct SumType {
     pub id: String,
+    /// Type parameters of a generic type such as `located<T>`.
+    pub params: Vec<String>,
     pub constructors: Vec<Constructor>,
     pub attributes: Vec<Field>,
-    pub comments: Vec<String>,
+    pub annotations: Vec<Annotation>,
+    pub comments: Vec<Comment>,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Constructor {
     pub id: String,
+    /// Stable discriminant for binary encodings, `Compound = 3`.
+    pub tag: Option<u32>,
     pub fields: Vec<Field>,
-    pub comments: Vec<String>,
+    pub annotations: Vec<Annotation>,
+    pub comments: Vec<Comment>,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ProdType {
     pub id: String,
+    pub params: Vec<String>,
     pub fields: Vec<Field>,
-    pub comments: Vec<String>,
+    pub attributes: Vec<Field>,
+    pub annotations: Vec<Annotation>,
+    pub comments: Vec<Comment>,
+    pub span: Span,
+}
+
+/// `name = identifier`, a distinct type wrapping `target`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AliasType {
+    pub id: String,
+    pub target: String,
+    pub annotations: Vec<Annotation>,
+    pub comments: Vec<Comment>,
+    pub span: Span,
+}
+
+/// `extern span`, a type defined outside of the schema by the code which
+/// uses it. `mappings` name it in target languages.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExternType {
+    pub id: String,
+    pub mappings: Vec<Mapping>,
+    pub annotations: Vec<Annotation>,
+    pub comments: Vec<Comment>,
+    pub span: Span,
+}
+
+impl ExternType {
+    /// Name of the type in `language`, as given by `language = "name"`.
+    pub fn mapping(&self, language: &str) -> Option<&str> {
+        self.mappings.iter().find(|m| m.language == language).map(|m| m.target.as_str())
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+/// `rust = "text_size::TextRange"`, the name of an extern type in a target
+/// language.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Mapping {
+    pub language: String,
+    pub target: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Field {
     pub id: String,
+    /// Stable field number for binary encodings, `1: stm body`. It comes
+    /// first since `= value` after the field name is its default value.
+    pub tag: Option<u32>,
+    /// Name of the referenced type, empty for inline tuple types. Resolve it
+    /// with `TypeIndex::field_type`.
     pub type_id: String,
+    /// Elements of an inline tuple type such as `(expr, expr)`, an anonymous
+    /// product type. Unnamed tuple fields are named `tuple`.
+    pub tuple: Option<Vec<Field>>,
+    /// Type arguments of a generic type such as `spanned<expr>`.
+    pub args: Vec<TypeArg>,
     pub arity: Arity,
+    /// Restriction on the values of a builtin type, `int(0..255)`.
+    pub constraint: Option<Constraint>,
+    /// Value of the field when it is omitted, `int level = 0`.
+    pub default: Option<Literal>,
+    pub annotations: Vec<Annotation>,
+    pub comments: Vec<Comment>,
+    pub span: Span,
+}
+
+/// Restriction on the values of a field with a builtin type. It applies to
+/// each value of sequences, optionals and maps.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Constraint {
+    /// `int(0..255)` or `int(>=0)`. Bounds are inclusive, `>0` and `<0` are
+    /// kept as `>=1` and `<=-1`.
+    Range { min: Option<i64>, max: Option<i64> },
+    /// `string(/[a-z_]+/)`, a regular expression which matches whole values.
+    /// `\/` is kept escaped.
+    Pattern(String),
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+/// Default value of a field.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Literal {
+    /// `0`, `-1`
+    Int(i64),
+    /// `true`, `false`
+    Bool(bool),
+    /// `"text"`, without the quotes.
+    String(String),
+    /// `None`, the default of optional fields.
+    None,
+    /// `Load`, a constructor without fields of the field type.
+    Constructor(String),
+}
+
+/// Type argument of a generic type, `type_id` may name a type parameter of
+/// the enclosing declaration.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TypeArg {
+    pub type_id: String,
+    pub args: Vec<TypeArg>,
+}
+
+/// Arity of a field. Modifiers compose, `expr?*` is a sequence of optional
+/// expressions: `Repeated(Optional(Required))`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Arity {
-    Optional,
+    /// `expr`
     Required,
-    Repeated,
+    /// `expr?`
+    Optional(Box<Arity>),
+    /// `expr*`
+    Repeated(Box<Arity>),
+    /// `expr+`, a sequence with at least one element.
+    NonEmpty(Box<Arity>),
+    /// `{identifier: expr}`, a map from `key` to values of the field type.
+    Map { key: String, value: Box<Arity> },
+}
+
+impl Arity {
+    /// Arity of the value of an optional, of the elements of a sequence or of
+    /// the values of a map.
+    pub fn inner(&self) -> Option<&Arity> {
+        match self {
+            Arity::Required => None,
+            Arity::Optional(inner) | Arity::Repeated(inner) | Arity::NonEmpty(inner) => Some(inner),
+            Arity::Map { value, .. } => Some(value),
+        }
+    }
+
+    pub fn is_sequence(&self) -> bool {
+        matches!(self, Arity::Repeated(_) | Arity::NonEmpty(_))
+    }
+}
+
+/// Comment above a declaration. Only doc comments document it, other
+/// comments are kept so that the schema can be printed back.
+///
+/// Trailing comments follow the declaration instead: they start on the line
+/// where it ends, or are the last thing before the `,`, `)` or `attributes`
+/// after it. At the root of the schema they are the comments at the end of
+/// the file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Comment {
+    pub text: String,
+    pub kind: CommentKind,
+    pub trailing: bool,
+    pub span: Span,
+}
+
+impl Comment {
+    pub fn is_doc(&self) -> bool {
+        self.kind == CommentKind::Doc
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommentKind {
+    /// `/// text` or `--| text`.
+    Doc,
+    /// `// text` or `-- text`.
+    Line,
+    /// `/* text */`.
+    Block,
+}
+
+/// Generator hint such as `@boxed` or `@rename("If_")`. The value is the
+/// text between the parentheses, without quotes if it was a string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Annotation {
+    pub name: String,
+    pub value: Option<String>,
+    pub span: Span,
 }
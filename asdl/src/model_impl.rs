@@ -1,91 +1,340 @@
 use crate::model::*;
 use crate::ast;
-use crate::util::FieldNames;
+use crate::builtins::Builtins;
+use crate::span::{SourceMap, Span};
+use crate::util::{FieldNames, TUPLE_TYPE_ID};
 
 impl Asdl {
-    pub(crate) fn new(root: &ast::Root) -> Self {
-        Asdl { types: root.types.iter().map(ty).collect(), comments: comments(&root.comments) }
+    pub(crate) fn new(src: &str, root: &ast::Root, builtins: Builtins, file: usize) -> Self {
+        let sm = SourceMap::new(src, file);
+        let mut module = root.module.as_ref().map(|m| module(m, &sm));
+        let imports: Vec<_> = root.imports.iter().map(|i| import(i, &sm)).collect();
+        let types: Vec<_> = root.types.iter().map(|t| ty(t, &sm)).collect();
+        // The parser collects comments at the start of the schema, before the
+        // closing `}` of the module and at the end of the file.
+        let end = module
+            .as_ref()
+            .map(|m| m.span)
+            .into_iter()
+            .chain(imports.iter().map(|i| i.span))
+            .chain(types.iter().map(Type::span))
+            .map(|s| s.end.offset)
+            .max()
+            .unwrap_or(0);
+        let mut comments = vec![];
+        for c in root.comments.iter() {
+            let span = sm.span(c.span.0);
+            let trailing = span.start.offset >= end;
+            let comment = Comment::new(c.text.to_string(), kind(c), trailing, span);
+            match &mut module {
+                Some(m) if !trailing && span.start.offset > m.span.start.offset => {
+                    m.comments.push(Comment { trailing: true, ..comment })
+                }
+                _ => comments.push(comment),
+            }
+        }
+        Asdl { module, imports, types, comments, builtins, files: vec![] }
+    }
+}
+
+impl Module {
+    fn new(id: String, version: Option<String>, span: Span) -> Self {
+        Module { id, version, comments: vec![], span }
+    }
+}
+
+fn module(m: &ast::Module, sm: &SourceMap) -> Module {
+    Module::new(m.id.to_string(), m.version.map(ToString::to_string), sm.span(m.span.0))
+}
+
+impl Import {
+    fn new(path: String, span: Span) -> Self {
+        Import { path, span }
     }
 }
 
-fn ty(ty: &ast::Type) -> Type {
+fn import(i: &ast::Import, sm: &SourceMap) -> Import {
+    Import::new(i.path.to_string(), sm.span(i.span.0))
+}
+
+fn ty(ty: &ast::Type, sm: &SourceMap) -> Type {
     match ty {
-        ast::Type::SumType(sty) => sum_type(sty).into(),
-        ast::Type::ProdType(pty) => prod_type(pty).into(),
+        ast::Type::SumType(sty) => sum_type(sty, sm).into(),
+        ast::Type::ProdType(pty) => prod_type(pty, sm).into(),
+        ast::Type::Alias(aty) => alias_type(aty, sm).into(),
+        ast::Type::Extern(ety) => extern_type(ety, sm).into(),
     }
 }
 
 impl SumType {
     fn new(
         id: String,
+        params: Vec<String>,
         constructors: Vec<Constructor>,
         attributes: Vec<Field>,
-        comments: Vec<String>,
+        annotations: Vec<Annotation>,
+        comments: Vec<Comment>,
+        span: Span,
     ) -> Self {
-        SumType { id, constructors, attributes, comments }
+        SumType { id, params, constructors, attributes, annotations, comments, span }
     }
 }
 
-fn sum_type(ty: &ast::SumType) -> SumType {
+fn sum_type(ty: &ast::SumType, sm: &SourceMap) -> SumType {
     let id = ty.type_id.to_string();
-    let constructors = ty.constructors.iter().map(constr).collect();
-    let attributes = ty.attrs.as_ref().map(|a| fields(&a.fields)).unwrap_or_default();
-    SumType::new(id, constructors, attributes, comments(&ty.comments))
+    let params = ty.params.iter().map(ToString::to_string).collect();
+    let constructors = ty.constructors.iter().map(|c| constr(c, sm)).collect();
+    let attributes = ty.attrs.as_ref().map(|a| fields(&a.fields, sm)).unwrap_or_default();
+    let annotations = annotations(&ty.annotations, sm);
+    let comments = comments(&ty.comments, &ty.span, sm);
+    let span = sm.span(ty.span.0);
+    SumType::new(id, params, constructors, attributes, annotations, comments, span)
 }
 
 impl Constructor {
-    fn new(id: String, fields: Vec<Field>, comments: Vec<String>) -> Self {
-        Constructor { id, fields, comments }
+    fn new(
+        id: String,
+        tag: Option<u32>,
+        fields: Vec<Field>,
+        annotations: Vec<Annotation>,
+        comments: Vec<Comment>,
+        span: Span,
+    ) -> Self {
+        Constructor { id, tag, fields, annotations, comments, span }
     }
 }
 
-fn constr(c: &ast::Constr) -> Constructor {
-    Constructor::new(c.id.to_string(), fields(&c.fields), comments(&c.comments))
+fn constr(c: &ast::Constr, sm: &SourceMap) -> Constructor {
+    let tag = c.tag.map(tag);
+    let fields = fields(&c.fields, sm);
+    let annotations = annotations(&c.annotations, sm);
+    let comments = comments(&c.comments, &c.span, sm);
+    Constructor::new(c.id.to_string(), tag, fields, annotations, comments, sm.span(c.span.0))
+}
+
+/// Converts a tag accepted by the parser.
+fn tag(tag: &str) -> u32 {
+    tag.parse().unwrap()
 }
 
 impl ProdType {
-    fn new(id: String, fields: Vec<Field>, comments: Vec<String>) -> Self {
-        ProdType { id, fields, comments }
+    fn new(
+        id: String,
+        params: Vec<String>,
+        fields: Vec<Field>,
+        attributes: Vec<Field>,
+        annotations: Vec<Annotation>,
+        comments: Vec<Comment>,
+        span: Span,
+    ) -> Self {
+        ProdType { id, params, fields, attributes, annotations, comments, span }
     }
 }
 
-fn prod_type(ty: &ast::ProdType) -> ProdType {
-    ProdType::new(ty.type_id.to_string(), fields(&ty.fields), comments(&ty.comments))
+fn prod_type(ty: &ast::ProdType, sm: &SourceMap) -> ProdType {
+    let id = ty.type_id.to_string();
+    let params = ty.params.iter().map(ToString::to_string).collect();
+    let attributes = ty.attrs.as_ref().map(|a| fields(&a.fields, sm)).unwrap_or_default();
+    let fields = fields(&ty.fields, sm);
+    let annotations = annotations(&ty.annotations, sm);
+    let comments = comments(&ty.comments, &ty.span, sm);
+    let span = sm.span(ty.span.0);
+    ProdType::new(id, params, fields, attributes, annotations, comments, span)
+}
+
+impl AliasType {
+    fn new(
+        id: String,
+        target: String,
+        annotations: Vec<Annotation>,
+        comments: Vec<Comment>,
+        span: Span,
+    ) -> Self {
+        AliasType { id, target, annotations, comments, span }
+    }
 }
 
-fn comments(comments: &Vec<&str>) -> Vec<String> {
-    comments.iter().map(ToString::to_string).collect()
+fn alias_type(ty: &ast::Alias, sm: &SourceMap) -> AliasType {
+    let id = ty.type_id.to_string();
+    let annotations = annotations(&ty.annotations, sm);
+    let comments = comments(&ty.comments, &ty.span, sm);
+    AliasType::new(id, ty.target.to_string(), annotations, comments, sm.span(ty.span.0))
 }
 
-fn fields(fields: &Vec<ast::Field>) -> Vec<Field> {
-    let mut names = FieldNames::default();
-    fields.iter().map(|f| field(f, &mut names)).collect()
+impl ExternType {
+    fn new(
+        id: String,
+        mappings: Vec<Mapping>,
+        annotations: Vec<Annotation>,
+        comments: Vec<Comment>,
+        span: Span,
+    ) -> Self {
+        ExternType { id, mappings, annotations, comments, span }
+    }
+}
+
+impl Mapping {
+    fn new(language: String, target: String, span: Span) -> Self {
+        Mapping { language, target, span }
+    }
+}
+
+fn extern_type(ty: &ast::Extern, sm: &SourceMap) -> ExternType {
+    let id = ty.type_id.to_string();
+    let mappings = ty
+        .mappings
+        .iter()
+        .map(|m| Mapping::new(m.language.to_string(), m.target.to_string(), sm.span(m.span.0)))
+        .collect();
+    let annotations = annotations(&ty.annotations, sm);
+    let comments = comments(&ty.comments, &ty.span, sm);
+    ExternType::new(id, mappings, annotations, comments, sm.span(ty.span.0))
+}
+
+impl Comment {
+    fn new(text: String, kind: CommentKind, trailing: bool, span: Span) -> Self {
+        Comment { text, kind, trailing, span }
+    }
+}
+
+/// Converts the comments of the declaration at `node`. The ones after its
+/// start are trailing.
+fn comments(comments: &[ast::Comment], node: &ast::Span, sm: &SourceMap) -> Vec<Comment> {
+    let start = sm.span(node.0).start.offset;
+    comments
+        .iter()
+        .map(|c| {
+            let span = sm.span(c.span.0);
+            Comment::new(c.text.to_string(), kind(c), span.start.offset > start, span)
+        })
+        .collect()
 }
 
-impl Field {
-    fn required(id: String, type_id: String) -> Self {
-        Field { id, type_id, arity: Arity::Required }
+fn kind(c: &ast::Comment) -> CommentKind {
+    let text = c.span.0;
+    if text.starts_with("///") || text.starts_with("--|") {
+        CommentKind::Doc
+    } else if text.starts_with("/*") {
+        CommentKind::Block
+    } else {
+        CommentKind::Line
     }
+}
 
-    fn optional(id: String, type_id: String) -> Self {
-        Field { id, type_id, arity: Arity::Optional }
+impl Annotation {
+    fn new(name: String, value: Option<String>, span: Span) -> Self {
+        Annotation { name, value, span }
     }
+}
+
+fn annotations(annotations: &[ast::Annotation], sm: &SourceMap) -> Vec<Annotation> {
+    annotations
+        .iter()
+        .map(|a| {
+            Annotation::new(a.name.to_string(), a.value.map(ToString::to_string), sm.span(a.span.0))
+        })
+        .collect()
+}
 
-    fn repeated(id: String, type_id: String) -> Self {
-        Field { id, type_id, arity: Arity::Repeated }
+fn fields(fields: &[ast::Field], sm: &SourceMap) -> Vec<Field> {
+    let mut names = FieldNames::default();
+    fields.iter().map(|f| field(f, &mut names, sm)).collect()
+}
+
+fn field(f: &ast::Field, names: &mut FieldNames, sm: &SourceMap) -> Field {
+    let (ty, arity) = type_ref(&f.ty);
+    let (type_id, args, mut tuple, constraint) = match ty {
+        ast::TypeRef::Tuple(t) => ("", vec![], Some(fields(&t.fields, sm)), None),
+        ast::TypeRef::Named(named) => {
+            let constraint = named.constraint.as_ref().map(self::constraint);
+            (named.type_id.0, type_args(&named.args), None, constraint)
+        }
+        ast::TypeRef::Map(_) => unreachable!(),
+    };
+    let name_type_id = ast::TypeId(if tuple.is_some() { TUPLE_TYPE_ID } else { type_id });
+    // Tuple types are printed on one line, comments in them trail the field.
+    let mut comments = comments(&f.comments, &f.span, sm);
+    for element in tuple.iter_mut().flatten() {
+        comments.extend(element.comments.drain(..).map(|c| Comment { trailing: true, ..c }));
+    }
+    comments.sort_by_key(|c| c.span.start.offset);
+    Field {
+        id: names.get_or_generate(&f.id, &name_type_id),
+        tag: f.tag.map(tag),
+        type_id: type_id.to_string(),
+        tuple,
+        args,
+        arity,
+        constraint,
+        default: f.default.map(literal),
+        annotations: annotations(&f.annotations, sm),
+        comments,
+        span: sm.span(f.span.0),
     }
 }
 
-fn field(f: &ast::Field, names: &mut FieldNames) -> Field {
-    match f {
-        ast::Field::Required(f) => {
-            Field::required(names.get_or_generate(&f.id, &f.type_id), f.type_id.to_string())
+/// Converts a constraint accepted by the parser. Strict bounds become
+/// inclusive ones, the parser rejects those which admit no value.
+fn constraint(c: &ast::Constraint) -> Constraint {
+    let int = |s: &str| s.parse::<i64>().unwrap();
+    match c {
+        ast::Constraint::Range(r) => {
+            Constraint::Range { min: Some(int(r.min)), max: Some(int(r.max)) }
         }
-        ast::Field::Optional(f) => {
-            Field::optional(names.get_or_generate(&f.id, &f.type_id), f.type_id.to_string())
+        ast::Constraint::Bound(b) => {
+            let value = int(b.value);
+            let (min, max) = match b.op {
+                ">=" => (Some(value), None),
+                ">" => (Some(value.checked_add(1).expect("empty bound")), None),
+                "<=" => (None, Some(value)),
+                _ => (None, Some(value.checked_sub(1).expect("empty bound"))),
+            };
+            Constraint::Range { min, max }
         }
-        ast::Field::Repeated(f) => {
-            Field::repeated(names.get_or_generate(&f.id, &f.type_id), f.type_id.to_string())
+        ast::Constraint::Pattern(p) => Constraint::Pattern(p.regex.to_string()),
+    }
+}
+
+/// Converts a default value accepted by the parser.
+fn literal(value: &str) -> Literal {
+    match value {
+        "true" => Literal::Bool(true),
+        "false" => Literal::Bool(false),
+        "None" => Literal::None,
+        _ if value.starts_with('"') => Literal::String(value[1..value.len() - 1].to_string()),
+        _ => match value.parse() {
+            Ok(n) => Literal::Int(n),
+            Err(_) => Literal::Constructor(value.to_string()),
+        },
+    }
+}
+
+fn type_args(args: &[ast::TypeArg]) -> Vec<TypeArg> {
+    args.iter()
+        .map(|a| TypeArg { type_id: a.type_id.to_string(), args: type_args(&a.args) })
+        .collect()
+}
+
+/// Returns the type of the values of `ty`, a named type or a tuple, and the
+/// arity around them.
+fn type_ref<'a, 'b>(ty: &'b ast::TypeRef<'a>) -> (&'b ast::TypeRef<'a>, Arity) {
+    match ty {
+        ast::TypeRef::Named(named) => (ty, arity(Arity::Required, named.arity)),
+        ast::TypeRef::Tuple(tuple) => (ty, arity(Arity::Required, tuple.arity)),
+        ast::TypeRef::Map(map) => {
+            let (type_id, value) = type_ref(&map.value);
+            let key = map.key.to_string();
+            (type_id, arity(Arity::Map { key, value: Box::new(value) }, map.arity))
         }
     }
 }
+
+/// Applies modifiers left to right, `?*` gives `Repeated(Optional(Required))`.
+fn arity(inner: Arity, modifiers: &str) -> Arity {
+    modifiers.chars().fold(inner, |inner, c| match c {
+        '?' => Arity::Optional(Box::new(inner)),
+        '*' => Arity::Repeated(Box::new(inner)),
+        '+' => Arity::NonEmpty(Box::new(inner)),
+        _ => unreachable!(),
+    })
+}
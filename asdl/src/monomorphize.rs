@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use crate::model::*;
+use crate::span::Span;
+use crate::validation::ValidationError;
+
+impl Asdl {
+    /// Replaces generic types with one concrete type per instantiation, for
+    /// templates whose target language has no generics.
+    ///
+    /// `spanned<expr>` becomes the type `spanned_expr`, constructors of generic
+    /// sum types get the type arguments appended: `Loc` of `located<expr>`
+    /// becomes `LocExpr`. Instantiations follow the other types in the order
+    /// they are first used. The schema is expected to be valid. Fails if a new
+    /// name is already taken by another type or constructor.
+    pub fn monomorphize(&self) -> std::result::Result<Asdl, Vec<ValidationError>> {
+        let (generics, concrete): (Vec<_>, Vec<_>) =
+            self.types.iter().partition(|t| !t.params().is_empty());
+        let mut mono = Monomorphizer {
+            generics: generics.into_iter().map(|t| (t.id().to_string(), t)).collect(),
+            instances: vec![],
+            names: HashMap::new(),
+            constructors: HashMap::new(),
+            errors: vec![],
+        };
+        for ty in concrete.iter() {
+            mono.names.insert(ty.id().to_string(), (ty.id().to_string(), ty.span()));
+            if let Type::SumType(sty) = ty {
+                for c in sty.constructors.iter() {
+                    let path = format!("{}.{}", sty.id, c.id);
+                    mono.constructors.insert(c.id.clone(), (path, c.span));
+                }
+            }
+        }
+        let mut types: Vec<_> = concrete.iter().map(|t| mono.ty(t, &HashMap::new())).collect();
+        let mut n = 0;
+        while n < mono.instances.len() {
+            let (id, args) = mono.instances[n].clone();
+            types.push(mono.instantiate(&id, &args));
+            n += 1;
+        }
+        if mono.errors.is_empty() {
+            Ok(Asdl { types, ..self.clone() })
+        } else {
+            Err(mono.errors)
+        }
+    }
+}
+
+/// Type arguments by the names of the parameters they replace.
+type Substitution<'a> = HashMap<&'a str, &'a TypeArg>;
+
+struct Monomorphizer<'a> {
+    generics: HashMap<String, &'a Type>,
+    /// Instantiations found so far, with arguments already replaced by the
+    /// names of concrete types.
+    instances: Vec<(String, Vec<TypeArg>)>,
+    /// Concrete type names with the type or instantiation they stand for and
+    /// where it is declared or first used.
+    names: HashMap<String, (String, Span)>,
+    /// Constructor ids with their `type.constructor` path and span.
+    constructors: HashMap<String, (String, Span)>,
+    errors: Vec<ValidationError>,
+}
+
+impl<'a> Monomorphizer<'a> {
+    fn instantiate(&mut self, id: &str, args: &[TypeArg]) -> Type {
+        let generic = self.generics[id];
+        let subst = generic.params().iter().map(String::as_str).zip(args).collect();
+        let suffix: String = args.iter().map(|a| camel(&a.type_id)).collect();
+        let instance = instance(id, args);
+        let mut ty = self.ty(generic, &subst);
+        match &mut ty {
+            Type::SumType(sty) => {
+                sty.id = instance_name(id, args);
+                sty.params.clear();
+                for c in sty.constructors.iter_mut() {
+                    let path = format!("{}.{}", instance, c.id);
+                    c.id.push_str(&suffix);
+                    claim(&mut self.constructors, &mut self.errors, &c.id, path, c.span);
+                }
+            }
+            Type::ProdType(pty) => {
+                pty.id = instance_name(id, args);
+                pty.params.clear();
+            }
+            Type::AliasType(_) | Type::ExternType(_) => unreachable!(),
+        }
+        ty
+    }
+
+    fn ty(&mut self, ty: &Type, subst: &Substitution) -> Type {
+        let mut ty = ty.clone();
+        match &mut ty {
+            Type::SumType(sty) => {
+                for c in sty.constructors.iter_mut() {
+                    self.fields(&mut c.fields, subst);
+                }
+                self.fields(&mut sty.attributes, subst);
+            }
+            Type::ProdType(pty) => {
+                self.fields(&mut pty.fields, subst);
+                self.fields(&mut pty.attributes, subst);
+            }
+            Type::AliasType(_) | Type::ExternType(_) => {}
+        }
+        ty
+    }
+
+    fn fields(&mut self, fields: &mut [Field], subst: &Substitution) {
+        for f in fields.iter_mut() {
+            if let Some(elements) = &mut f.tuple {
+                self.fields(elements, subst);
+                continue;
+            }
+            let arg = TypeArg { type_id: f.type_id.clone(), args: f.args.clone() };
+            let arg = self.type_arg(&arg, subst, f.span);
+            f.type_id = arg.type_id;
+            f.args = arg.args;
+        }
+    }
+
+    /// Substitutes type parameters in `arg` and replaces instantiations of
+    /// generic types with the names of the concrete types. `span` is the
+    /// field which uses `arg`.
+    fn type_arg(&mut self, arg: &TypeArg, subst: &Substitution, span: Span) -> TypeArg {
+        if let Some(value) = subst.get(arg.type_id.as_str()) {
+            return TypeArg::clone(value);
+        }
+        let args: Vec<_> = arg.args.iter().map(|a| self.type_arg(a, subst, span)).collect();
+        if !self.generics.contains_key(&arg.type_id) {
+            return TypeArg { type_id: arg.type_id.clone(), args };
+        }
+        let type_id = instance_name(&arg.type_id, &args);
+        let instance = instance(&arg.type_id, &args);
+        if claim(&mut self.names, &mut self.errors, &type_id, instance, span) {
+            self.instances.push((arg.type_id.clone(), args));
+        }
+        TypeArg { type_id, args: vec![] }
+    }
+}
+
+/// Reserves the type or constructor `name` in `names` for `instance`.
+/// Returns whether it was free, reports a clash if something else has it.
+fn claim(
+    names: &mut HashMap<String, (String, Span)>,
+    errors: &mut Vec<ValidationError>,
+    name: &str,
+    instance: String,
+    span: Span,
+) -> bool {
+    match names.get(name) {
+        Some((other, _)) if *other == instance => false,
+        Some((other, first)) => {
+            errors.push(ValidationError::InstanceClash {
+                name: name.to_string(),
+                instance,
+                other: other.clone(),
+                span,
+                first: *first,
+            });
+            false
+        }
+        None => {
+            names.insert(name.to_string(), (instance, span));
+            true
+        }
+    }
+}
+
+/// `spanned<located<expr>>` with the arguments already named, as
+/// `spanned<located_expr>`.
+fn instance(id: &str, args: &[TypeArg]) -> String {
+    let args: Vec<_> = args.iter().map(|a| a.type_id.as_str()).collect();
+    format!("{}<{}>", id, args.join(", "))
+}
+
+/// `spanned<located<expr>>` is named `spanned_located_expr`.
+fn instance_name(id: &str, args: &[TypeArg]) -> String {
+    let mut name = id.to_string();
+    for arg in args {
+        name.push('_');
+        name.push_str(&arg.type_id);
+    }
+    name
+}
+
+fn camel(id: &str) -> String {
+    let mut res = String::new();
+    for part in id.split('_') {
+        let mut chars = part.chars();
+        if let Some(c) = chars.next() {
+            res.extend(c.to_uppercase());
+            res.push_str(chars.as_str());
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monomorphize_instantiations() {
+        let asdl = r"
+            stm = Expr(spanned<expr> value) | Body(located<spanned<stm>>* body)
+            expr = Name(identifier id, spanned<identifier>? ctx)
+            spanned<T> = (T node, int start, int end)
+            located<T> = Loc(T value) | Missing attributes (int line)
+            ";
+        let model = Asdl::parse(asdl).unwrap();
+        assert_eq!(model.validate(), Ok(()));
+        let mono = model.monomorphize().unwrap();
+        assert_eq!(mono.validate(), Ok(()));
+        assert_eq!(
+            mono.to_string(),
+            "\
+stm = Expr(spanned_expr value) | Body(located_spanned_stm* body)
+expr = Name(identifier id, spanned_identifier? ctx)
+spanned_expr = (expr node, int start, int end)
+spanned_stm = (stm node, int start, int end)
+located_spanned_stm = LocSpannedStm(spanned_stm value) | MissingSpannedStm attributes (int line)
+spanned_identifier = (identifier node, int start, int end)
+"
+        );
+    }
+
+    #[test]
+    fn report_name_clashes() {
+        let asdl = r"
+            stm = Expr(spanned<expr> value, pair<a_b, c> p, pair<a, b_c> q)
+                | LocExpr(located<expr> e)
+            expr = Name(identifier id)
+            spanned_expr = (expr node)
+            a_b = (int x)
+            b_c = (int x)
+            a = (int x)
+            c = (int x)
+            spanned<T> = (T node, int start, int end)
+            pair<K, V> = (K key, V value)
+            located<T> = Loc(T value) | Missing
+            ";
+        let model = Asdl::parse(asdl).unwrap();
+        assert_eq!(model.validate(), Ok(()));
+        let errors = model.monomorphize().unwrap_err();
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "Monomorphized name `spanned_expr` of `spanned<expr>` is already used by \
+                 `spanned_expr`",
+                "Monomorphized name `pair_a_b_c` of `pair<a, b_c>` is already used by \
+                 `pair<a_b, c>`",
+                "Monomorphized name `LocExpr` of `located<expr>.Loc` is already used by \
+                 `stm.LocExpr`",
+            ]
+        );
+    }
+}
@@ -1,205 +1,710 @@
-use nom::IResult;
+use nom::{Err, IResult};
 
 use super::ast::*;
+use crate::error::{AsdlError, ErrorKind};
+use crate::span::SourceMap;
 
 use std::ops::{RangeFrom, RangeTo};
 
 use nom::{Slice, InputIter, Offset, AsChar, InputTakeAtPosition};
-use nom::error::{ParseError, VerboseError, context, VerboseErrorKind, ErrorKind};
+use nom::error::ParseError;
 use nom::character::is_alphanumeric;
-use nom::character::complete::{char, one_of};
-use nom::sequence::{pair, tuple};
-use nom::bytes::complete::{take_while_m_n, take_while, is_a, tag};
+use nom::character::complete::{anychar, char, none_of, one_of};
+use nom::sequence::{pair, tuple, preceded, terminated, delimited, separated_pair};
+use nom::bytes::complete::{take_while_m_n, take_while, take_until, tag};
+use nom::multi::{many0, many1, separated_nonempty_list};
 use nom::branch::alt;
-use nom::multi::{separated_list, many1, separated_nonempty_list};
-use nom::combinator::{map, opt, recognize, peek};
-use nom::character::complete::{multispace0, multispace1, line_ending, not_line_ending, space0};
+use nom::combinator::{map, not, opt, recognize, peek, cut, verify};
+use nom::character::complete::{multispace0, multispace1, line_ending, not_line_ending, space0, digit1};
 
+/// Parse error trace. The innermost failure goes first, followed by the
+/// kinds and contexts of the enclosing parsers.
 #[derive(Clone, Debug, PartialEq)]
-pub struct Error<I> {
-    /// list of errors accumulated by `VerboseError`, containing the affected
-    /// part of input data, and some context
-    pub errors: std::vec::Vec<(I, VerboseErrorKind)>,
+pub(crate) struct Error<I> {
+    pub(crate) errors: Vec<(I, Entry)>,
 }
 
-impl<I> Into<VerboseError<I>> for Error<I> {
-    fn into(self) -> VerboseError<I> {
-        VerboseError { errors: self.errors }
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Entry {
+    Nom(nom::error::ErrorKind),
+    Char(char),
+    Kind(ErrorKind),
+    Context(&'static str),
+}
+
+impl<I> Error<I> {
+    fn add_kind(input: I, kind: ErrorKind, mut other: Self) -> Self {
+        if let Some((_, Entry::Nom(_))) = other.errors.last() {
+            other.errors.pop();
+        }
+        other.errors.push((input, Entry::Kind(kind)));
+        other
     }
 }
 
 impl<I> ParseError<I> for Error<I> {
-    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
-        Error { errors: vec![(input, VerboseErrorKind::Nom(kind))] }
+    fn from_error_kind(input: I, kind: nom::error::ErrorKind) -> Self {
+        Error { errors: vec![(input, Entry::Nom(kind))] }
     }
 
-    fn append(input: I, kind: ErrorKind, mut other: Self) -> Self {
-        other.errors.push((input, VerboseErrorKind::Nom(kind)));
+    fn append(input: I, kind: nom::error::ErrorKind, mut other: Self) -> Self {
+        other.errors.push((input, Entry::Nom(kind)));
         other
     }
 
     fn from_char(input: I, c: char) -> Self {
-        Error { errors: vec![(input, VerboseErrorKind::Char(c))] }
+        Error { errors: vec![(input, Entry::Char(c))] }
     }
 
     fn add_context(input: I, ctx: &'static str, mut other: Self) -> Self {
-        if let Some((_, VerboseErrorKind::Nom(_))) = other.errors.last() {
+        if let Some((_, Entry::Nom(_))) = other.errors.last() {
             other.errors.pop();
         }
-        other.errors.push((input, VerboseErrorKind::Context(ctx)));
+        other.errors.push((input, Entry::Context(ctx)));
         other
     }
 }
 
 type ParseResult<'a, T> = IResult<&'a str, T, Error<&'a str>>;
 
-pub(crate) fn parse(i: &str) -> ParseResult<Root> {
-    if i.is_empty() {
-        return Ok((i, Root::new(vec![], vec![])));
+/// Parses a whole schema. Syntax errors do not stop the parser: each one is
+/// recorded and parsing resumes at the next type declaration (a line starting
+/// with `lowercase_id =`) or, inside a sum type, at the next `|`. The returned
+/// root holds everything that could be parsed. Its comments are the ones at
+/// the start of the schema, before the closing `}` of the module and at the
+/// end of the file.
+pub(crate) fn parse(src: &str) -> (Root<'_>, Vec<AsdlError>) {
+    let mut errors = vec![];
+    let (i, mut comments) = root_comments(src);
+    let (start, imports) = imports(i, &mut errors);
+    let (i, header, in_module) = match module_header(start) {
+        Ok((i, header)) => (i, Some(header), true),
+        Err(Err::Error(_)) => (start, None, false),
+        Err(e) => {
+            let i = &start[recovery_point(start, &e, None)..];
+            errors.push(e);
+            (i, None, true)
+        }
+    };
+    let (mut i, types) = types(i, in_module, &mut errors);
+    if in_module {
+        match module_close(i) {
+            Ok((rest, mut closing)) => {
+                comments.append(&mut closing);
+                i = rest;
+            }
+            Err(e) => errors.push(e),
+        }
     }
-    map(
-        tuple((
-            multispace0,
-            opt(pair(comments, multispace1)),
-            multispace0,
-            context("Expected at least one type declaration", many1(ty)),
-        )),
-        |(_, comments, _, types)| Root::new(types, comments.map(|c| c.0).unwrap_or_default()),
-    )(i)
+    let module = header.map(|(id, version)| Module::new(id, version, span(start, i)));
+    match trailing(i) {
+        Ok((_, mut trailing)) => comments.append(&mut trailing),
+        Err(e) => errors.push(e),
+    }
+    let errors = errors.into_iter().map(|e| error(src, e)).collect();
+    (Root::new(module, imports, types, comments), errors)
 }
 
-fn comment_line(i: &str) -> ParseResult<&str> {
-    let (i, (_, _, _, comment)) = tuple((
-        space0,
-        context("Comment line should start with '//'", tag("//")),
-        space0,
-        not_line_ending,
+/// Parses the comments at the start of the schema. They must be separated
+/// from the next declaration by an empty line, otherwise they belong to it.
+fn root_comments(i: &str) -> (&str, Vec<Comment<'_>>) {
+    let (i, _) = multispace0::<_, Error<&str>>(i).unwrap_or((i, ""));
+    let res: ParseResult<'_, _> =
+        terminated(comments, tuple((line_ending, space0, line_ending, multispace0)))(i);
+    match res {
+        Ok((i, comments)) => (i, comments),
+        Err(_) => (i, vec![]),
+    }
+}
+
+fn imports<'a>(
+    mut i: &'a str,
+    errors: &mut Vec<Err<Error<&'a str>>>,
+) -> (&'a str, Vec<Import<'a>>) {
+    let mut imports = vec![];
+    loop {
+        match import(i) {
+            Ok((rest, import)) => {
+                imports.push(import);
+                i = rest;
+            }
+            Err(Err::Error(_)) => return (i, imports),
+            Err(e) => {
+                i = &i[recovery_point(i, &e, None)..];
+                errors.push(e);
+            }
+        }
+    }
+}
+
+fn import(i: &str) -> ParseResult<'_, Import<'_>> {
+    let keyword = tuple((tag("import"), multispace1, peek(char('"'))));
+    let (i, (path, span)) = spanned(preceded(keyword, cut(string)))(i)?;
+    let (i, _) = multispace0(i)?;
+    Ok((i, Import::new(path, span)))
+}
+
+fn module_header(i: &str) -> ParseResult<'_, (Id<'_>, Option<&str>)> {
+    let (i, (_, _, id)) = tuple((tag("module"), multispace1, id))(i)?;
+    let (i, version) = cut(terminated(
+        opt(preceded(tuple((multispace1, tag("version"), multispace1)), cut(string))),
+        expect(ErrorKind::MissingModuleBody, char_ms0('{')),
     ))(i)?;
-    Ok((i, comment))
+    Ok((i, (id, version)))
 }
 
-fn comments(i: &str) -> ParseResult<Vec<&str>> {
-    separated_list(line_ending, comment_line)(i)
+fn module_close(i: &str) -> ParseResult<'_, Vec<Comment<'_>>> {
+    terminated(free_comments, expect(ErrorKind::UnterminatedModule, char_ms0('}')))(i)
 }
 
-fn ty(i: &str) -> ParseResult<Type> {
-    context(
-        "Expected Product or Sum type declaration",
-        alt((map(prod_type, |t| t.into()), map(sum_type, |t| t.into()))),
-    )(i)
+/// Parses type declarations up to the end of input, or up to the closing `}`
+/// of a module.
+fn types<'a>(
+    mut i: &'a str,
+    in_module: bool,
+    errors: &mut Vec<Err<Error<&'a str>>>,
+) -> (&'a str, Vec<Type<'a>>) {
+    let mut types = vec![];
+    while trailing(i).is_err() {
+        if let Ok((rest, _)) = module_close(i) {
+            if in_module {
+                break;
+            }
+            let brace = i[..i.offset(rest)].trim_end().len() - 1;
+            errors
+                .push(Err::Error(Error::from_error_kind(&i[brace..], nom::error::ErrorKind::Eof)));
+            i = rest;
+            continue;
+        }
+        match ty(i, errors) {
+            Ok((rest, t)) => {
+                types.push(t);
+                i = rest;
+            }
+            Err(e) => {
+                i = &i[recovery_point(i, &e, None)..];
+                errors.push(e);
+            }
+        }
+    }
+    (i, types)
 }
 
-fn prod_type(i: &str) -> ParseResult<ProdType> {
-    map(
-        tuple((comments, multispace0, type_id, char_ms0('='), fields)),
-        |(comments, _, type_id, _, fields)| ProdType::new(type_id, fields, comments),
-    )(i)
+/// Succeeds if only whitespace and comments are left.
+fn trailing(i: &str) -> ParseResult<'_, Vec<Comment<'_>>> {
+    let (i, comments) = free_comments(i)?;
+    if i.is_empty() {
+        Ok((i, comments))
+    } else {
+        Err(Err::Error(Error::from_error_kind(i, nom::error::ErrorKind::Eof)))
+    }
 }
 
-fn sum_type(i: &str) -> ParseResult<SumType> {
-    map(
-        tuple((
-            multispace0,
-            comments,
-            opt(line_ending),
-            space0,
-            type_id,
-            char_ms0('='),
-            constructors,
-            opt(attrs),
-        )),
-        |(_, comments, _, _, type_id, _, constructors, attrs)| {
-            SumType::new(type_id, constructors, attrs, comments)
-        },
-    )(i)
+/// Offset in `i` of the innermost failure of `err`.
+fn error_offset(i: &str, err: &Err<Error<&str>>) -> usize {
+    match err {
+        Err::Error(e) | Err::Failure(e) => e.errors.first().map_or(i.len(), |e| i.offset(e.0)),
+        Err::Incomplete(_) => i.len(),
+    }
 }
 
-fn attrs(i: &str) -> ParseResult<Attrs> {
-    map(pair(is_a("attributes"), fields), |(_, flds)| Attrs::new(flds))(i)
+/// Finds where parsing of the declaration at the start of `i` can resume
+/// after `err`: the next line which starts a type declaration (together with
+/// the comments right above it), or the first `}` or `separator` following
+/// the error outside of map braces, whichever comes first.
+fn recovery_point(i: &str, err: &Err<Error<&str>>, separator: Option<char>) -> usize {
+    let from = error_offset(i, err);
+    let skipped: ParseResult<'_, _> = tuple((multispace0, comments, multispace0))(i);
+    let start = skipped.map_or(0, |(rest, _)| i.offset(rest));
+    let start = annotations(&i[start..]).map_or(start, |(rest, _)| i.offset(rest));
+    let next_line = i[start..].find('\n').map_or(i.len(), |n| start + n + 1);
+    let mut boundary = i.len();
+    let mut offset = next_line;
+    let mut comments_start = None;
+    for line in i[next_line..].split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let decl = annotations(trimmed).map_or(trimmed, |(rest, _)| rest);
+        let is_comment = ["//", "--", "/*"].iter().any(|m| trimmed.starts_with(m));
+        if is_comment || (decl.is_empty() && trimmed.starts_with('@')) {
+            comments_start.get_or_insert(offset);
+        } else if decl.starts_with('}')
+            || tuple((type_id, type_params, equals))(decl).is_ok()
+            || extern_keyword(decl).is_ok()
+        {
+            boundary = comments_start.unwrap_or(offset);
+            break;
+        } else {
+            comments_start = None;
+        }
+        offset += line.len();
+    }
+    if from >= boundary {
+        return boundary;
+    }
+    let mut depth = 0;
+    for (n, c) in i[start..boundary].char_indices() {
+        let at_depth0 = depth == 0 && start + n >= from;
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            '}' if at_depth0 => return start + n,
+            c if at_depth0 && Some(c) == separator => return start + n,
+            _ => {}
+        }
+    }
+    boundary
+}
+
+fn string(i: &str) -> ParseResult<'_, &str> {
+    delimited(char('"'), take_while(|c| c != '"'), expect(ErrorKind::UnterminatedString, char('"')))(
+        i,
+    )
+}
+
+/// Parses a `//` or `--` line comment, a `///` or `--|` doc comment, or a
+/// `/* */` block comment.
+fn comment(i: &str) -> ParseResult<'_, Comment<'_>> {
+    let (start, _) = space0(i)?;
+    let markers = alt((tag("///"), tag("--|"), tag("//"), tag("--"), tag("/*")));
+    let (i, marker) = expect(ErrorKind::BadComment, markers)(start)?;
+    let (i, text) = if marker == "/*" {
+        let close = expect(ErrorKind::UnterminatedComment, pair(take_until("*/"), tag("*/")));
+        map(cut(close), |(text, _): (&str, _)| text.trim())(i)?
+    } else {
+        preceded(space0, not_line_ending)(i)?
+    };
+    Ok((i, Comment::new(text, span(start, i))))
 }
 
-fn constructors(i: &str) -> ParseResult<Vec<Constr>> {
-    separated_nonempty_list(char_ms0('|'), constructor)(i)
+/// Parses comments on consecutive lines. An empty line ends the list.
+fn comments(i: &str) -> ParseResult<'_, Vec<Comment<'_>>> {
+    let mut comments = vec![];
+    let mut rest = i;
+    loop {
+        let next = if comments.is_empty() { rest } else { pair(space0, opt(line_ending))(rest)?.0 };
+        match comment(next) {
+            Ok((i, comment)) => {
+                comments.push(comment);
+                rest = i;
+            }
+            Err(Err::Error(_)) => return Ok((rest, comments)),
+            Err(e) => return Err(e),
+        }
+    }
 }
 
-fn constructor(i: &str) -> ParseResult<Constr> {
+/// Parses whitespace and the comments in it, also across empty lines.
+fn free_comments(i: &str) -> ParseResult<'_, Vec<Comment<'_>>> {
+    terminated(many0(preceded(multispace0, comment)), multispace0)(i)
+}
+
+/// Parses a comment on the current line which trails the declaration before
+/// it. Doc comments document the declaration after them instead.
+fn trailing_comment(i: &str) -> ParseResult<'_, Comment<'_>> {
+    verify(comment, |c: &Comment| !c.span.0.starts_with("///") && !c.span.0.starts_with("--|"))(i)
+}
+
+/// Parses the comments on the line where the declaration starting at
+/// `start` and covering `span` ends. They trail the declaration. `i` is the
+/// input after the declaration, parsing continues there if there are none.
+fn line_comments<'a>(
+    start: &'a str,
+    span: &Span<'a>,
+    i: &'a str,
+) -> ParseResult<'a, Vec<Comment<'a>>> {
+    let end = &start[start.offset(span.0) + span.0.len()..];
+    let (rest, comments) = many0(trailing_comment)(end)?;
+    // The declaration may end with a part which took them already.
+    if comments.is_empty() || end.offset(rest) <= end.offset(i) {
+        return Ok((i, vec![]));
+    }
+    let (rest, _) = multispace0(rest)?;
+    Ok((rest, comments))
+}
+
+/// Skips whitespace and comments which are not attached to any declaration,
+/// such as comments in an empty field list.
+fn trivia(i: &str) -> ParseResult<'_, &str> {
+    recognize(free_comments)(i)
+}
+
+fn ty<'a>(i: &'a str, errors: &mut Vec<Err<Error<&'a str>>>) -> ParseResult<'a, Type<'a>> {
+    let keyword = tuple((multispace0, comments, multispace0, annotations, extern_keyword));
+    if keyword(i).is_ok() {
+        return map(extern_type, Type::from)(i);
+    }
+    let head = tuple((multispace0, comments, multispace0, annotations, type_id, type_params));
+    let product = tuple((head, equals, peek(char('('))));
+    if product(i).is_ok() {
+        return map(prod_type, Type::from)(i);
+    }
+    let alias = tuple((multispace0, comments, multispace0, annotations, type_id, equals, type_id));
+    if alias(i).is_ok() {
+        return map(alias_type, Type::from)(i);
+    }
+    match sum_type(i, errors) {
+        Ok((i, t)) => Ok((i, t.into())),
+        Err(e) => Err(e.map(|e| Error::add_kind(i, ErrorKind::ExpectedTypeDeclaration, e))),
+    }
+}
+
+fn prod_type(i: &str) -> ParseResult<'_, ProdType<'_>> {
+    let (start, (mut comments, _, annotations)) = tuple((comments, multispace0, annotations))(i)?;
+    let (i, ((type_id, params, _, mut fields, attrs), span)) =
+        spanned(tuple((type_id, type_params, equals, fields, opt(attrs))))(start)?;
+    let attrs = attrs.map(|(mut before, attrs)| {
+        match fields.last_mut() {
+            Some(field) => field.comments.append(&mut before),
+            None => comments.append(&mut before),
+        }
+        attrs
+    });
+    let (i, mut trailing) = line_comments(start, &span, i)?;
+    comments.append(&mut trailing);
+    Ok((i, ProdType::new(type_id, params, fields, attrs, annotations, comments, span)))
+}
+
+/// Parses `name = identifier`, a new type wrapping `identifier`.
+fn alias_type(i: &str) -> ParseResult<'_, Alias<'_>> {
+    let (start, (mut comments, _, annotations)) = tuple((comments, multispace0, annotations))(i)?;
+    let (i, ((type_id, _, target), span)) = spanned(tuple((type_id, equals, type_id)))(start)?;
+    let (i, mut trailing) = line_comments(start, &span, i)?;
+    comments.append(&mut trailing);
+    Ok((i, Alias::new(type_id, target, annotations, comments, span)))
+}
+
+/// Parses `extern span`, a type defined outside of the schema, with optional
+/// names in target languages: `extern span(rust = "text_size::TextRange")`.
+fn extern_type(i: &str) -> ParseResult<'_, Extern<'_>> {
+    let (start, (mut comments, _, annotations)) = tuple((comments, multispace0, annotations))(i)?;
+    let (i, ((_, (type_id, mappings)), span)) =
+        spanned(pair(extern_keyword, cut(pair(type_id, mappings))))(start)?;
+    let (i, mut trailing) = line_comments(start, &span, i)?;
+    comments.append(&mut trailing);
+    Ok((i, Extern::new(type_id, mappings, annotations, comments, span)))
+}
+
+/// `extern` followed by whitespace, unless it is the name of a type.
+fn extern_keyword(i: &str) -> ParseResult<'_, &str> {
+    recognize(tuple((tag("extern"), multispace1, not(char('=')))))(i)
+}
+
+fn mappings(i: &str) -> ParseResult<'_, Vec<Mapping<'_>>> {
+    let mapping =
+        map(spanned(separated_pair(id, char_ms0('='), string)), |((lang, target), span)| {
+            Mapping::new(lang, target, span)
+        });
+    let mappings = separated_nonempty_list(char_ms0(','), expect(ErrorKind::BadMapping, mapping));
+    let close = expect(ErrorKind::UnterminatedMappings, preceded(multispace0, char(')')));
+    map(opt(preceded(char_ms0('('), cut(terminated(mappings, close)))), Option::unwrap_or_default)(
+        i,
+    )
+}
+
+fn sum_type<'a>(i: &'a str, errors: &mut Vec<Err<Error<&'a str>>>) -> ParseResult<'a, SumType<'a>> {
+    let (i, (_, mut comments, _, _)) = tuple((multispace0, comments, opt(line_ending), space0))(i)?;
+    let (start, annotations) = annotations(i)?;
+    let (i, (type_id, params, _)) = tuple((type_id, type_params, equals))(start)?;
+    let (i, mut constructors) = constructors(i, errors)?;
+    let (i, attrs) = opt(attrs)(i)?;
+    // Comments after the last constructor are not part of the type.
+    let last =
+        attrs.as_ref().map(|(_, attrs)| &attrs.span).or(constructors.last().map(|c| &c.span));
+    let span = match last {
+        Some(last) => Span(&start[..start.offset(last.0) + last.0.len()]),
+        None => span(start, i),
+    };
+    let attrs = attrs.map(|(mut before, attrs)| {
+        match constructors.last_mut() {
+            Some(constr) => constr.comments.append(&mut before),
+            None => comments.append(&mut before),
+        }
+        attrs
+    });
+    let (i, mut trailing) = line_comments(start, &span, i)?;
+    comments.append(&mut trailing);
+    Ok((i, SumType::new(type_id, params, constructors, attrs, annotations, comments, span)))
+}
+
+/// Parses the optional `<T, U>` after the name of a declaration.
+fn type_params(i: &str) -> ParseResult<'_, Vec<Id<'_>>> {
+    let params = separated_nonempty_list(char_ms0(','), id);
+    let close = expect(ErrorKind::UnterminatedTypeParams, char_ms0('>'));
+    map(opt(preceded(char_ms0('<'), cut(terminated(params, close)))), Option::unwrap_or_default)(i)
+}
+
+/// Parses the attributes of a type, together with the comments before them.
+fn attrs(i: &str) -> ParseResult<'_, (Vec<Comment<'_>>, Attrs<'_>)> {
+    let attrs =
+        map(spanned(pair(tag("attributes"), fields)), |((_, flds), span)| Attrs::new(flds, span));
+    pair(free_comments, attrs)(i)
+}
+
+/// Parses `|` separated constructors. A broken constructor is recorded in
+/// `errors` and parsing continues with the constructor after the next `|`.
+fn constructors<'a>(
+    mut i: &'a str,
+    errors: &mut Vec<Err<Error<&'a str>>>,
+) -> ParseResult<'a, Vec<Constr<'a>>> {
+    let mut constructors = vec![];
+    let mut leading = vec![];
+    loop {
+        match constructor(i) {
+            Ok((rest, mut constr)) => {
+                leading.append(&mut constr.comments);
+                constr.comments = leading;
+                constructors.push(constr);
+                i = rest;
+            }
+            Err(e) => {
+                i = &i[recovery_point(i, &e, Some('|'))..];
+                errors.push(e);
+            }
+        }
+        // Comments on the line of the `|` belong to the constructor before
+        // it, other ones before `|` to the constructor after it.
+        let bar = tuple((
+            multispace0,
+            comments,
+            preceded(multispace0, char('|')),
+            many0(trailing_comment),
+            multispace0,
+        ));
+        match bar(i) {
+            Ok((rest, (_, comments, _, mut trailing, _))) => {
+                if let Some(constr) = constructors.last_mut() {
+                    constr.comments.append(&mut trailing);
+                }
+                leading = comments;
+                i = rest;
+            }
+            Err(Err::Error(_)) => return Ok((i, constructors)),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn constructor(i: &str) -> ParseResult<'_, Constr<'_>> {
     let (i, _) = multispace0(i)?;
-    let (i, comments) = comments(i)?;
+    let (i, mut comments) = comments(i)?;
     let (i, _) = multispace0(i)?;
-    let (i, con_id) = con_id(i)?;
+    let (start, annotations) = annotations(i)?;
+    let tag = opt(preceded(char_ms0('='), cut(expect(ErrorKind::BadTag, tag_number))));
+    let (i, ((con_id, tag, fields), span)) =
+        spanned(tuple((con_id, tag, constructor_fields)))(start)?;
+    let (i, mut trailing) = line_comments(start, &span, i)?;
+    comments.append(&mut trailing);
+    Ok((i, Constr::new(con_id, tag, fields, annotations, comments, span)))
+}
+
+/// Parses a constructor or field tag, a number which fits into a `u32`.
+fn tag_number(i: &str) -> ParseResult<'_, &str> {
+    verify(digit1, |s: &str| s.parse::<u32>().is_ok())(i)
+}
+
+/// Parses the tag before a field, `1:`. Field tags come first, Thrift
+/// style, because `= value` after a field name is its default value.
+fn tag_prefix(i: &str) -> ParseResult<'_, &str> {
+    let number = expect(ErrorKind::BadTag, tag_number);
+    let colon = expect(ErrorKind::MissingTagColon, char_ms0(':'));
+    preceded(peek(digit1), cut(terminated(number, colon)))(i)
+}
+
+fn constructor_fields(i: &str) -> ParseResult<'_, Vec<Field<'_>>> {
     let (i, _) = multispace0(i)?;
     let (i, next) = opt(peek(char('(')))(i)?;
-    let (i, fields) = if let Some(_) = next { fields(i)? } else { (i, vec![]) };
-    Ok((i, Constr::new(con_id, fields, comments)))
+    if next.is_some() {
+        fields(i)
+    } else {
+        Ok((i, vec![]))
+    }
 }
 
-fn fields(i: &str) -> ParseResult<Vec<Field>> {
-    let fields = separated_list(char_ms0(','), field);
-    map(tuple((char_ms0('('), fields, char_ms0(')'))), |(_, fields, _)| fields)(i)
+fn fields(i: &str) -> ParseResult<'_, Vec<Field<'_>>> {
+    let close = expect(ErrorKind::UnterminatedFieldList, preceded(trivia, char_ms0(')')));
+    map(tuple((char_ms0('('), |i| field_list(i, false), close)), |(_, fields, _)| fields)(i)
 }
 
-fn field(i: &str) -> ParseResult<Field> {
-    let (i, type_id) = type_id(i)?;
-    let (i, arity) = peek(context("Expected * ? ) , or whitespace.", one_of("*? ),")))(i)?;
-    let (i, arity) = match arity {
-        '*' | '?' => one_of("*?")(i)?,
-        _ => (i, ' '),
-    };
-    let (i, name) = opt(pair(multispace1, id))(i)?;
-    let name = name.map(|n| n.1);
-    match arity {
-        '*' => Ok((i, Repeated::new(type_id, name).into())),
-        '?' => Ok((i, Optional::new(type_id, name).into())),
-        ' ' => Ok((i, Required::new(type_id, name).into())),
-        _ => unreachable!(),
+/// Parses `,` separated fields, at least one if `nonempty` is set. Comments
+/// between a field and the `,` or `)` after it, and the ones on the line of
+/// the `,`, belong to the field.
+fn field_list(mut i: &str, nonempty: bool) -> ParseResult<'_, Vec<Field<'_>>> {
+    let mut fields = vec![];
+    let mut end = i;
+    loop {
+        let (rest, mut field) = match field(i) {
+            Ok(res) => res,
+            Err(Err::Error(_)) if !nonempty || !fields.is_empty() => break,
+            Err(e) => return Err(e),
+        };
+        let (rest, mut trailing) = free_comments(rest)?;
+        field.comments.append(&mut trailing);
+        end = rest;
+        let next = match tuple((char(','), many0(trailing_comment), multispace0))(rest) {
+            Ok((next, (_, mut trailing, _))) => {
+                field.comments.append(&mut trailing);
+                Some(next)
+            }
+            Err(Err::Error(_)) => None,
+            Err(e) => return Err(e),
+        };
+        fields.push(field);
+        match next {
+            Some(next) => i = next,
+            None => break,
+        }
     }
+    Ok((end, fields))
 }
 
-fn type_id(i: &str) -> ParseResult<TypeId> {
+/// Parses a field such as `1: stm* body = None`.
+fn field(i: &str) -> ParseResult<'_, Field<'_>> {
+    let (i, (comments, _, annotations)) = tuple((comments, multispace0, annotations))(i)?;
+    let (i, ((tag, ty, name, default), span)) =
+        spanned(tuple((opt(tag_prefix), type_ref, opt(preceded(multispace1, id)), opt(default))))(
+            i,
+        )?;
+    Ok((i, Field::new(tag, ty, name, default, annotations, comments, span)))
+}
+
+/// Parses `= literal` after a field: an integer, `true`, `false`, a string,
+/// `None` or a constructor id. The literal is kept as written.
+fn default(i: &str) -> ParseResult<'_, &str> {
+    let ident = recognize(pair(
+        take_while_m_n(1, 1, is_uppercase),
+        take_while(is_alphanumeric_or_underscore),
+    ));
+    let literal = alt((int, recognize(string), tag("true"), tag("false"), ident));
+    preceded(char_ms0('='), cut(expect(ErrorKind::BadDefault, literal)))(i)
+}
+
+/// Parses an integer which fits into an `i64`.
+fn int(i: &str) -> ParseResult<'_, &str> {
+    verify(recognize(pair(opt(char('-')), digit1)), |s: &str| s.parse::<i64>().is_ok())(i)
+}
+
+/// Parses the optional constraint directly after a type name: a range such
+/// as `(0..255)`, a bound such as `(>=0)` or a pattern such as `(/[a-z]+/)`.
+fn constraint(i: &str) -> ParseResult<'_, Option<Constraint<'_>>> {
+    let range = map(separated_pair(int, tag(".."), int), |(min, max)| Range::new(min, max).into());
+    let pattern = map(pattern, |regex| Pattern::new(regex).into());
+    let constraint = expect(ErrorKind::BadConstraint, alt((range, bound, pattern)));
+    let close = expect(ErrorKind::UnterminatedConstraint, preceded(multispace0, char(')')));
+    opt(preceded(pair(char('('), multispace0), cut(terminated(constraint, close))))(i)
+}
+
+/// Parses a bound such as `>=0`. A strict bound must leave some value, `>`
+/// the largest `i64` admits none.
+fn bound(i: &str) -> ParseResult<'_, Constraint<'_>> {
+    let (i, op) = terminated(alt((tag(">="), tag("<="), tag(">"), tag("<"))), multispace0)(i)?;
+    let admits_value =
+        |value: &str| !matches!((op, value.parse()), (">", Ok(i64::MAX)) | ("<", Ok(i64::MIN)));
+    let value = cut(expect(ErrorKind::EmptyBound, verify(int, admits_value)));
+    let (i, value) = preceded(peek(int), value)(i)?;
+    Ok((i, Bound::new(op, value).into()))
+}
+
+/// Parses a `/regex/`, `\/` escapes a slash. Returns the text between the
+/// slashes as written.
+fn pattern(i: &str) -> ParseResult<'_, &str> {
+    let chars = many1(alt((recognize(pair(char('\\'), anychar)), recognize(none_of("\\/\r\n")))));
+    let close = expect(ErrorKind::UnterminatedPattern, char('/'));
+    preceded(tag("/"), cut(terminated(recognize(chars), close)))(i)
+}
+
+/// Parses a type id, a `{key: value}` map or a `(expr, expr)` tuple,
+/// followed by arity modifiers.
+fn type_ref(i: &str) -> ParseResult<'_, TypeRef<'_>> {
+    if i.starts_with('(') {
+        let elements = |i| field_list(i, true);
+        let close = expect(ErrorKind::UnterminatedFieldList, preceded(trivia, char(')')));
+        let (i, (_, (fields, _, arity))) =
+            pair(char_ms0('('), cut(tuple((elements, close, arity))))(i)?;
+        return Ok((i, Tuple::new(fields, arity).into()));
+    }
+    if !i.starts_with('{') {
+        let (i, (type_id, args, constraint, arity)) =
+            tuple((type_name, type_args, constraint, arity))(i)?;
+        return Ok((i, Named::new(type_id, args, constraint, arity).into()));
+    }
+    let colon = expect(ErrorKind::MissingColon, char_ms0(':'));
+    let close = expect(ErrorKind::UnterminatedMap, preceded(multispace0, char('}')));
+    let (i, (_, (key, _, value, _, arity))) =
+        pair(char_ms0('{'), cut(tuple((type_id, colon, type_ref, close, arity))))(i)?;
+    Ok((i, Map::new(key, Box::new(value), arity).into()))
+}
+
+/// Parses the optional `<expr, spanned<T>>` after a type name. The `>` is
+/// not followed by whitespace so that arity modifiers can be checked.
+fn type_args(i: &str) -> ParseResult<'_, Vec<TypeArg<'_>>> {
+    let type_arg = map(pair(type_name, type_args), |(type_id, args)| TypeArg::new(type_id, args));
+    let args = separated_nonempty_list(char_ms0(','), type_arg);
+    let close = expect(ErrorKind::UnterminatedTypeArgs, preceded(multispace0, char('>')));
+    map(opt(preceded(char_ms0('<'), cut(terminated(args, close)))), Option::unwrap_or_default)(i)
+}
+
+/// Type name in a field: a type id or a type parameter such as `T`.
+fn type_name(i: &str) -> ParseResult<'_, TypeId<'_>> {
+    alt((map(con_id, |c| TypeId(c.0)), type_id))(i)
+}
+
+/// Parses annotations such as `@boxed` or `@rename("If_")`, each followed
+/// by optional whitespace.
+fn annotations(i: &str) -> ParseResult<'_, Vec<Annotation<'_>>> {
+    many0(terminated(annotation, multispace0))(i)
+}
+
+/// The value of an annotation is either a string or the raw text between
+/// the parentheses.
+fn annotation(i: &str) -> ParseResult<'_, Annotation<'_>> {
+    let raw = map(take_while(|c| c != ')'), str::trim);
+    let close = expect(ErrorKind::UnterminatedAnnotation, char(')'));
+    let value =
+        preceded(char('('), cut(terminated(alt((delimited(space0, string, space0), raw)), close)));
+    let (i, ((name, value), span)) = spanned(preceded(char('@'), cut(pair(id, opt(value)))))(i)?;
+    Ok((i, Annotation::new(name, value, span)))
+}
+
+/// Parses arity modifiers such as `*`, `+`, `?` or `?*`.
+fn arity(i: &str) -> ParseResult<'_, &str> {
+    let (i, _) = peek(expect(ErrorKind::BadArity, one_of("*?+ ),}")))(i)?;
+    take_while(|c| c == '*' || c == '?' || c == '+')(i)
+}
+
+fn type_id(i: &str) -> ParseResult<'_, TypeId<'_>> {
     map(
         recognize(pair(
-            context(
-                "Type Id should start with lowercase character",
-                take_while_m_n(1, 1, is_lowercase),
-            ),
-            context(
-                "The rest of Type Id should be alphanumeric or underscore",
-                take_while(is_alphanumeric_or_underscore),
-            ),
+            expect(ErrorKind::BadTypeIdCase, take_while_m_n(1, 1, is_lowercase)),
+            take_while(is_alphanumeric_or_underscore),
         )),
         TypeId,
     )(i)
 }
 
-fn con_id(i: &str) -> ParseResult<ConstrId> {
+fn con_id(i: &str) -> ParseResult<'_, ConstrId<'_>> {
     map(
         recognize(pair(
-            context(
-                "Constructor Id should start with uppercase character",
-                take_while_m_n(1, 1, is_uppercase),
-            ),
-            context(
-                "The rest of Constructor Id should be alphanumeric or underscore",
-                take_while(is_alphanumeric_or_underscore),
-            ),
+            expect(ErrorKind::BadConstructorIdCase, take_while_m_n(1, 1, is_uppercase)),
+            take_while(is_alphanumeric_or_underscore),
         )),
         ConstrId,
     )(i)
 }
 
-fn id(i: &str) -> ParseResult<Id> {
+fn id(i: &str) -> ParseResult<'_, Id<'_>> {
     map(
         recognize(pair(
-            context("Id should start with alpha character", take_while_m_n(1, 1, is_alpha)),
-            context(
-                "The rest of Id should be alphanumeric or underscore",
-                take_while(is_alphanumeric_or_underscore),
-            ),
+            expect(ErrorKind::BadIdentifier, take_while_m_n(1, 1, is_alpha)),
+            take_while(is_alphanumeric_or_underscore),
         )),
         Id,
     )(i)
 }
 
+fn equals(i: &str) -> ParseResult<'_, &str> {
+    expect(ErrorKind::MissingEquals, char_ms0('='))(i)
+}
+
 fn is_alpha(a: char) -> bool {
     is_alphanumeric(a as u8)
 }
@@ -226,20 +731,92 @@ where
     recognize(tuple((multispace0, char(c), multispace0)))
 }
 
+/// Classifies errors of `f` as `kind`.
+fn expect<'a, O, F>(kind: ErrorKind, f: F) -> impl Fn(&'a str) -> ParseResult<'a, O>
+where
+    F: Fn(&'a str) -> ParseResult<'a, O>,
+{
+    move |i: &'a str| f(i).map_err(|e| e.map(|e| Error::add_kind(i, kind, e)))
+}
+
+/// Runs `f` and pairs its output with the source text it consumed.
+/// Trailing whitespace is not part of the span.
+fn spanned<'a, O, F>(f: F) -> impl Fn(&'a str) -> ParseResult<'a, (O, Span<'a>)>
+where
+    F: Fn(&'a str) -> ParseResult<'a, O>,
+{
+    move |i: &'a str| {
+        let (rest, o) = f(i)?;
+        Ok((rest, (o, span(i, rest))))
+    }
+}
+
+/// Source text between `start` and `end` without trailing whitespace.
+fn span<'a>(start: &'a str, end: &'a str) -> Span<'a> {
+    Span(start[..start.offset(end)].trim_end())
+}
+
 fn is_alphanumeric_or_underscore(a: char) -> bool {
     is_alphanumeric(a as u8) || a == '_'
 }
 
-pub(crate) fn convert_error(input: &str, err: Error<&str>) -> String {
-    nom::error::convert_error(input, err.into())
+pub(crate) fn error(src: &str, err: Err<Error<&str>>) -> AsdlError {
+    let sm = SourceMap::new(src, 0);
+    let errors = match err {
+        Err::Error(e) | Err::Failure(e) => e.errors,
+        Err::Incomplete(_) => vec![],
+    };
+    let input = errors.first().map_or(&src[src.len()..], |e| e.0);
+    let len = input.chars().next().map_or(0, char::len_utf8);
+    let span = sm.span(&input[..len]);
+    let kind_index = errors.iter().position(|e| matches!(e.1, Entry::Kind(_)));
+    let kind = match kind_index.map(|i| &errors[i].1) {
+        Some(Entry::Kind(kind)) => *kind,
+        _ if input.is_empty() => ErrorKind::UnexpectedEof,
+        _ => ErrorKind::UnexpectedToken,
+    };
+    let mut error = AsdlError::new(kind, span);
+    for (n, (i, entry)) in errors.iter().enumerate() {
+        match entry {
+            Entry::Char(c) if i.as_ptr() == input.as_ptr() => {
+                let c = format!("'{}'", c);
+                if !error.expected.contains(&c) {
+                    error.expected.push(c);
+                }
+            }
+            Entry::Kind(kind) if Some(n) != kind_index => {
+                error.notes.push(kind.message().to_string())
+            }
+            Entry::Context(ctx) => error.notes.push(ctx.to_string()),
+            _ => (),
+        }
+    }
+    if error.expected.is_empty() {
+        error.expected = expected(kind).iter().map(ToString::to_string).collect();
+    }
+    error
+}
+
+fn expected(kind: ErrorKind) -> &'static [&'static str] {
+    match kind {
+        ErrorKind::BadTypeIdCase => &["type id"],
+        ErrorKind::BadConstructorIdCase => &["constructor id"],
+        ErrorKind::BadIdentifier => &["identifier"],
+        ErrorKind::BadComment => &["'//'", "'--'", "'/*'"],
+        ErrorKind::BadArity => &["'*'", "'+'", "'?'", "','", "')'", "field name"],
+        ErrorKind::BadTag => &["tag number"],
+        ErrorKind::BadDefault => {
+            &["integer", "string", "'true'", "'false'", "'None'", "constructor id"]
+        }
+        ErrorKind::BadConstraint => &["range", "'>='", "'<='", "'>'", "'<'", "pattern"],
+        _ => &[],
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
-    use nom;
-    use nom::Err;
     use insta::assert_debug_snapshot_matches;
     use std::fmt::Debug;
 
@@ -267,11 +844,7 @@ mod tests {
         assert_error::<TypeId>(
             type_id,
             "ABcd1Efg",
-            "
-0: at line 0, in Type Id should start with lowercase character:
-ABcd1Efg
-^
-            ",
+            "1:1: Type Id should start with lowercase character, expected type id",
         );
     }
 
@@ -282,11 +855,7 @@ ABcd1Efg
         assert_error::<ConstrId>(
             con_id,
             "aBcd1Efg",
-            "
-0: at line 0, in Constructor Id should start with uppercase character:
-aBcd1Efg
-^
-            ",
+            "1:1: Constructor Id should start with uppercase character, expected constructor id",
         );
     }
 
@@ -299,42 +868,153 @@ aBcd1Efg
         assert_error::<Id>(
             id,
             "_aBcd1Efg",
-            "
-0: at line 0, in Id should start with alpha character:
-_aBcd1Efg
-^
-            ",
+            "1:1: Id should start with alpha character, expected identifier",
         );
     }
 
     #[test]
     fn parse_field() {
-        assert_eq!(field("type,"), Ok((",", Required::new(TypeId("type"), None).into())));
-        assert_eq!(field("type?,"), Ok((",", Optional::new(TypeId("type"), None).into())));
-        assert_eq!(field("type*,"), Ok((",", Repeated::new(TypeId("type"), None).into())));
-        assert_eq!(field("type)"), Ok((")", Required::new(TypeId("type"), None).into())));
-        assert_eq!(field("type "), Ok((" ", Required::new(TypeId("type"), None).into())));
+        assert_eq!(
+            field("type,"),
+            Ok((
+                ",",
+                Field::new(
+                    None,
+                    Named::new(TypeId("type"), vec![], None, "").into(),
+                    None,
+                    None,
+                    vec![],
+                    vec![],
+                    Span("type")
+                )
+            ))
+        );
+        assert_eq!(
+            field("type?,"),
+            Ok((
+                ",",
+                Field::new(
+                    None,
+                    Named::new(TypeId("type"), vec![], None, "?").into(),
+                    None,
+                    None,
+                    vec![],
+                    vec![],
+                    Span("type?")
+                )
+            ))
+        );
+        assert_eq!(
+            field("type*,"),
+            Ok((
+                ",",
+                Field::new(
+                    None,
+                    Named::new(TypeId("type"), vec![], None, "*").into(),
+                    None,
+                    None,
+                    vec![],
+                    vec![],
+                    Span("type*")
+                )
+            ))
+        );
+        assert_eq!(
+            field("type)"),
+            Ok((
+                ")",
+                Field::new(
+                    None,
+                    Named::new(TypeId("type"), vec![], None, "").into(),
+                    None,
+                    None,
+                    vec![],
+                    vec![],
+                    Span("type")
+                )
+            ))
+        );
+        assert_eq!(
+            field("type "),
+            Ok((
+                " ",
+                Field::new(
+                    None,
+                    Named::new(TypeId("type"), vec![], None, "").into(),
+                    None,
+                    None,
+                    vec![],
+                    vec![],
+                    Span("type")
+                )
+            ))
+        );
 
         assert_eq!(
             field("type  name,"),
-            Ok((",", Required::new(TypeId("type"), Some(Id("name"))).into()))
+            Ok((
+                ",",
+                Field::new(
+                    None,
+                    Named::new(TypeId("type"), vec![], None, "").into(),
+                    Some(Id("name")),
+                    None,
+                    vec![],
+                    vec![],
+                    Span("type  name")
+                )
+            ))
         );
         assert_eq!(
             field("type?  name,"),
-            Ok((",", Optional::new(TypeId("type"), Some(Id("name"))).into()))
+            Ok((
+                ",",
+                Field::new(
+                    None,
+                    Named::new(TypeId("type"), vec![], None, "?").into(),
+                    Some(Id("name")),
+                    None,
+                    vec![],
+                    vec![],
+                    Span("type?  name")
+                )
+            ))
         );
         assert_eq!(
             field("type*  name,"),
-            Ok((",", Repeated::new(TypeId("type"), Some(Id("name"))).into()))
+            Ok((
+                ",",
+                Field::new(
+                    None,
+                    Named::new(TypeId("type"), vec![], None, "*").into(),
+                    Some(Id("name")),
+                    None,
+                    vec![],
+                    vec![],
+                    Span("type*  name")
+                )
+            ))
+        );
+        assert_eq!(
+            field("type?*+ name,"),
+            Ok((
+                ",",
+                Field::new(
+                    None,
+                    Named::new(TypeId("type"), vec![], None, "?*+").into(),
+                    Some(Id("name")),
+                    None,
+                    vec![],
+                    vec![],
+                    Span("type?*+ name")
+                )
+            ))
         );
         assert_error::<Field>(
             field,
-            "type+  name",
-            "
-0: at line 0, in Expected * ? ) , or whitespace.:
-type+  name
-    ^
-            ",
+            "type!  name",
+            "1:5: Unexpected character after field type, \
+             expected '*' or '+' or '?' or ',' or ')' or field name",
         );
     }
 
@@ -345,20 +1025,31 @@ type+  name
             Ok((
                 "",
                 vec![
-                    Required::new(TypeId("type1"), None).into(),
-                    Optional::new(TypeId("type2"), Some(Id("name"))).into()
+                    Field::new(
+                        None,
+                        Named::new(TypeId("type1"), vec![], None, "").into(),
+                        None,
+                        None,
+                        vec![],
+                        vec![],
+                        Span("type1")
+                    ),
+                    Field::new(
+                        None,
+                        Named::new(TypeId("type2"), vec![], None, "?").into(),
+                        Some(Id("name")),
+                        None,
+                        vec![],
+                        vec![],
+                        Span("type2? name")
+                    )
                 ]
             ))
         );
         assert_error::<Vec<Field>>(
             fields,
             " ( type1 type2? name  ) ",
-            "
-0: at line 0:
- ( type1 type2? name  ) 
-              ^
-expected ')', found ?
-            ",
+            "1:15: Unterminated field list, expected ')'",
         );
     }
 
@@ -370,82 +1061,162 @@ expected ')', found ?
                 "",
                 Constr::new(
                     ConstrId("ConstrId"),
+                    None,
                     vec![
-                        Required::new(TypeId("type1"), None).into(),
-                        Optional::new(TypeId("type2"), Some(Id("name"))).into()
+                        Field::new(
+                            None,
+                            Named::new(TypeId("type1"), vec![], None, "").into(),
+                            None,
+                            None,
+                            vec![],
+                            vec![],
+                            Span("type1")
+                        ),
+                        Field::new(
+                            None,
+                            Named::new(TypeId("type2"), vec![], None, "?").into(),
+                            Some(Id("name")),
+                            None,
+                            vec![],
+                            vec![],
+                            Span("type2? name")
+                        )
                     ],
-                    vec![]
+                    vec![],
+                    vec![],
+                    Span("ConstrId( type1, type2? name  )")
                 )
             ))
         );
 
         assert_eq!(
             constructor("ConstrId"),
-            Ok(("", Constr::new(ConstrId("ConstrId"), vec![], vec![])))
+            Ok((
+                "",
+                Constr::new(ConstrId("ConstrId"), None, vec![], vec![], vec![], Span("ConstrId"))
+            ))
         );
         assert_error::<Constr>(
             constructor,
             "ConstrId1( type1,",
-            "
-0: at line 0:
-ConstrId1( type1,
-                ^
-expected ')', found ,
-            ",
+            "1:17: Unterminated field list, expected ')'",
         );
     }
 
     #[test]
     fn parse_constructors() {
         assert_eq!(
-            constructors("ConstrId1( type1, type2? name  ) | ConstrId2"),
+            constructors("ConstrId1( type1, type2? name  ) | ConstrId2", &mut vec![]),
             Ok((
                 "",
                 vec![
                     Constr::new(
                         ConstrId("ConstrId1"),
+                        None,
                         vec![
-                            Required::new(TypeId("type1"), None).into(),
-                            Optional::new(TypeId("type2"), Some(Id("name"))).into()
+                            Field::new(
+                                None,
+                                Named::new(TypeId("type1"), vec![], None, "").into(),
+                                None,
+                                None,
+                                vec![],
+                                vec![],
+                                Span("type1")
+                            ),
+                            Field::new(
+                                None,
+                                Named::new(TypeId("type2"), vec![], None, "?").into(),
+                                Some(Id("name")),
+                                None,
+                                vec![],
+                                vec![],
+                                Span("type2? name")
+                            )
                         ],
-                        vec![]
+                        vec![],
+                        vec![],
+                        Span("ConstrId1( type1, type2? name  )")
                     ),
-                    Constr::new(ConstrId("ConstrId2"), vec![], vec![])
+                    Constr::new(
+                        ConstrId("ConstrId2"),
+                        None,
+                        vec![],
+                        vec![],
+                        vec![],
+                        Span("ConstrId2")
+                    )
                 ]
             ))
         );
-        assert_error::<Vec<Constr>>(
-            constructors,
-            "ConstrId1( type1, type2? name    ConstrId2",
-            "
-0: at line 0:
-ConstrId1( type1, type2? name    ConstrId2
-                                 ^
-expected ')', found C
-            ",
+        let mut errors = vec![];
+        let asdl = "ConstrId1( type1, type2? name    ConstrId2 | ConstrId3";
+        assert_eq!(
+            constructors(asdl, &mut errors),
+            Ok((
+                "",
+                vec![Constr::new(
+                    ConstrId("ConstrId3"),
+                    None,
+                    vec![],
+                    vec![],
+                    vec![],
+                    Span("ConstrId3")
+                )]
+            ))
         );
+        let errors: Vec<_> = errors.into_iter().map(|e| error(asdl, e).to_string()).collect();
+        assert_eq!(errors, vec!["1:34: Unterminated field list, expected ')'"]);
     }
 
     #[test]
-    fn parse_comment_line() {
+    fn parse_comment() {
         let asdl = "  //comment line1";
-        assert_eq!(comment_line(asdl), Ok(("", "comment line1")));
-        assert_error::<&str>(
-            comment_line,
+        assert_eq!(comment(asdl), Ok(("", Comment::new("comment line1", Span("//comment line1")))));
+        assert_eq!(comment("-- line\n"), Ok(("\n", Comment::new("line", Span("-- line")))));
+        assert_eq!(comment("--| doc"), Ok(("", Comment::new("doc", Span("--| doc")))));
+        assert_eq!(comment("/// doc"), Ok(("", Comment::new("doc", Span("/// doc")))));
+        assert_eq!(
+            comment("/* block\n  comment */ stm"),
+            Ok((" stm", Comment::new("block\n  comment", Span("/* block\n  comment */"))))
+        );
+        assert_error::<Comment>(
+            comment,
             "/comment line1",
-            "
-0: at line 0, in Comment line should start with '//':
-/comment line1
-^
-            ",
+            "1:1: Comment should start with '//', '--' or '/*', expected '//' or '--' or '/*'",
         );
+        assert_error::<Comment>(comment, "/* comment", "1:3: Unterminated block comment");
     }
 
     #[test]
     fn parse_comments() {
         let asdl = "  // comment line1
                         // comment line2";
-        assert_eq!(comments(asdl), Ok(("", vec!["comment line1", "comment line2"])));
+        assert_eq!(
+            comments(asdl),
+            Ok((
+                "",
+                vec![
+                    Comment::new("comment line1", Span("// comment line1")),
+                    Comment::new("comment line2", Span("// comment line2"))
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_comment_styles() {
+        let asdl = r"
+            -- CPython style
+            mod = Module(stmt* body)
+                -- not really an actual node
+                | Expression(expr body /* the body */)
+
+                -- col_offset is the byte offset
+                attributes (int lineno)
+            /// Statement
+            stm = Pass -- trailing
+            ";
+        assert_debug_snapshot_matches!("parse_comment_styles", parse(asdl));
     }
 
     #[test]
@@ -455,7 +1226,10 @@ expected ')', found C
                         // ConstrId2 comment line1
                         // ConstrId2 comment line2
                         ConstrId2";
-        assert_debug_snapshot_matches!("parse_constructors_with_comments", constructors(asdl));
+        assert_debug_snapshot_matches!(
+            "parse_constructors_with_comments",
+            constructors(asdl, &mut vec![])
+        );
     }
 
     #[test]
@@ -469,7 +1243,7 @@ expected ')', found C
                         // ConstrId2 comment line1
                         // ConstrId2 comment line2
                         ConstrId2";
-        assert_debug_snapshot_matches!("parse_sum_type", sum_type(asdl));
+        assert_debug_snapshot_matches!("parse_sum_type", sum_type(asdl, &mut vec![]));
     }
 
     #[test]
@@ -480,49 +1254,333 @@ expected ')', found C
         assert_debug_snapshot_matches!("parse_prod_type", prod_type(asdl));
     }
 
+    #[test]
+    fn parse_prod_type_attributes() {
+        let asdl = r#"arg = (identifier arg, expr? annotation)
+                        attributes (int lineno, int col_offset)"#;
+        assert_debug_snapshot_matches!("parse_prod_type_attributes", prod_type(asdl));
+    }
+
+    #[test]
+    fn parse_alias_type() {
+        let asdl = r#"// name comment
+                        @derive(Clone)
+                        name = identifier
+                        stm = Pass"#;
+        assert_debug_snapshot_matches!("parse_alias_type", parse(asdl));
+    }
+
     #[test]
     fn parse_empty_asdl() {
         let asdl = "";
         assert_debug_snapshot_matches!("parse_empty_asdl", parse(asdl));
     }
 
+    #[test]
+    fn parse_root_comments() {
+        fn type_comments<'a>(root: &Root<'a>) -> Vec<&'a str> {
+            match &root.types[0] {
+                Type::SumType(ty) => ty.comments.iter().map(|c| c.text).collect(),
+                _ => unreachable!(),
+            }
+        }
+        // Without an empty line the comments document the first type.
+        let (root, _) = parse("// schema\n\n// stm\nstm = Pass\n");
+        assert_eq!(root.comments, vec![Comment::new("schema", Span("// schema"))]);
+        assert_eq!(type_comments(&root), vec!["stm"]);
+        let (root, _) = parse("// stm\nstm = Pass -- pass\n// end\n");
+        assert_eq!(root.comments, vec![Comment::new("end", Span("// end"))]);
+        assert_eq!(type_comments(&root), vec!["stm"]);
+        let (root, _) = parse("module M {\n    stm = Pass\n    // close\n}\n// end");
+        let comments: Vec<_> = root.comments.iter().map(|c| c.text).collect();
+        assert_eq!(comments, vec!["close", "end"]);
+    }
+
+    #[test]
+    fn parse_module() {
+        let asdl = r#"
+            // Root comment
+
+            import "base.asdl"
+            module Simple version "1.0" {
+                stm = Compound(stm s1, stm* s2) | Single(stm)
+                // Trailing comment
+            }"#;
+        assert_debug_snapshot_matches!("parse_module", parse(asdl));
+        assert_eq!(
+            parse("module Empty {}"),
+            (
+                Root::new(
+                    Some(Module::new(Id("Empty"), None, Span("module Empty {}"))),
+                    vec![],
+                    vec![],
+                    vec![]
+                ),
+                vec![]
+            )
+        );
+    }
+
+    #[test]
+    fn parse_error_unclosed_module() {
+        assert_parse_errors(
+            "module Simple { stm = Single ",
+            &["1:30: Unterminated module, expected '}'"],
+        );
+    }
+
     #[test]
     fn parse_error_invalid_single_type() {
         let asdl = r"
 // comment line 1
 // comment line 2
 notType&";
-        assert_error::<Root>(
-            parse,
+        assert_parse_errors(
             asdl,
-            r"
-0: at line 3:
-notType&
-       ^
-expected '=', found &
+            &["4:8: Missing '=' after type id, expected '='
+note: Expected Product or Sum type declaration"],
+        );
+    }
 
-1: at line 3, in Expected Product or Sum type declaration:
-notType&
-^
+    #[test]
+    fn parse_error_recovery() {
+        let asdl = r"
+            stm = Compound(stm s1 stm s2)
+                | Single(stm)
+                | Broken(stm s1,
+                | Empty
+            expr = Name(identifier id)
+            // next type
+            bad = (expr x expr y)
+            extern span(rust = Span)
+            extern loc
+            mod = Module(stm* body)
+                attributes (int lineno)
+            Trailing
+            slice = (expr lower)";
+        assert_parse_errors(
+            asdl,
+            &[
+                "2:35: Unterminated field list, expected ')'",
+                "4:32: Unterminated field list, expected ')'",
+                "8:27: Unterminated field list, expected ')'",
+                "9:32: Invalid extern mapping, expected '\"'",
+                "13:13: Type Id should start with lowercase character, expected type id
+note: Expected Product or Sum type declaration",
+            ],
+        );
+        let (root, _) = parse(asdl);
+        let ids: Vec<_> = root
+            .types
+            .iter()
+            .map(|t| match t {
+                Type::SumType(t) => t.type_id.0,
+                Type::ProdType(t) => t.type_id.0,
+                Type::Alias(t) => t.type_id.0,
+                Type::Extern(t) => t.type_id.0,
+            })
+            .collect();
+        assert_eq!(ids, vec!["stm", "expr", "loc", "mod", "slice"]);
+        assert_debug_snapshot_matches!("parse_error_recovery", root.types[0]);
+    }
 
-2: at line 3, in Expected at least one type declaration:
-notType&
-^
-            ",
+    #[test]
+    fn parse_error_trailing_input() {
+        assert_parse_errors("module M { stm = Single } stm", &["1:27: Unexpected token"]);
+        assert_parse_errors("stm = Single\n}\nexpr = Name", &["2:1: Unexpected token"]);
+    }
+
+    #[test]
+    fn parse_field_comments() {
+        let asdl = "Compound(// the body\n  // first\n  stm s1, // rest\n  stm* s2)";
+        assert_debug_snapshot_matches!("parse_field_comments", constructor(asdl));
+    }
+
+    #[test]
+    fn parse_annotations() {
+        let asdl = r#"@derive(Clone, Debug) @boxed
+                        stm = @rename("If_") If(@boxed expr test) | Pass"#;
+        assert_debug_snapshot_matches!("parse_annotations", sum_type(asdl, &mut vec![]));
+    }
+
+    #[test]
+    fn parse_annotation_errors() {
+        let asdl = r#"
+            stm = If(@rename("x" expr test)
+            @boxed
+            expr = (@ int)
+            "#;
+        assert_parse_errors(
+            asdl,
+            &[
+                "2:34: Unterminated annotation arguments, expected ')'",
+                "4:22: Id should start with alpha character, expected identifier",
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_map_fields() {
+        let asdl =
+            "Call(expr func, {identifier: expr} keywords, { string : {int: expr?}* }? nested)";
+        assert_debug_snapshot_matches!("parse_map_fields", constructor(asdl));
+    }
+
+    #[test]
+    fn parse_tuple_fields() {
+        let asdl = "Dict((expr, expr)* items, ( string name , int? )? pair)";
+        assert_debug_snapshot_matches!("parse_tuple_fields", constructor(asdl));
+    }
+
+    #[test]
+    fn parse_tuple_errors() {
+        let asdl = r#"
+            stm = Dict((expr; expr)* items)
+            expr = Tuple(()* items)
+            "#;
+        assert_parse_errors(
+            asdl,
+            &[
+                "2:29: Unexpected character after field type, expected '*' or '+' or '?' or ',' or ')' or field name",
+                "3:27: Type Id should start with lowercase character, expected type id",
+            ],
         );
     }
 
+    #[test]
+    fn parse_generic_types() {
+        let asdl = "spanned<T, U> = (T node, located<U, expr>? loc)";
+        assert_debug_snapshot_matches!("parse_generic_types", prod_type(asdl));
+    }
+
+    #[test]
+    fn parse_generic_errors() {
+        let asdl = r#"
+            spanned<T = (T node)
+            stm = Expr(spanned<expr value)
+            expr = Name
+            "#;
+        assert_parse_errors(
+            asdl,
+            &[
+                "2:23: Unterminated type parameter list, expected '>'
+note: Expected Product or Sum type declaration",
+                "3:37: Unterminated type argument list, expected '>'",
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_map_errors() {
+        let asdl = r#"
+            stm = Call({identifier expr} keywords)
+            expr = ({identifier: expr keywords)
+            "#;
+        assert_parse_errors(
+            asdl,
+            &[
+                "2:36: Missing ':' after map key type, expected ':'",
+                "3:39: Unterminated map type, expected '}'",
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_field_defaults() {
+        let asdl = r#"Def(int level = -1, string doc="", expr? = None, ctx ctx = Load)"#;
+        assert_debug_snapshot_matches!("parse_field_defaults", constructor(asdl));
+    }
+
+    #[test]
+    fn parse_default_errors() {
+        let asdl = r#"
+            stm = Pass(int depth = )
+            expr = (string s = "abc, int n)
+            "#;
+        let expected =
+            "expected integer or string or 'true' or 'false' or 'None' or constructor id";
+        assert_parse_errors(
+            asdl,
+            &[
+                &format!("2:36: Invalid default value, {}", expected),
+                &format!("3:32: Invalid default value, {}", expected),
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_tags() {
+        let asdl = "Compound = 3 ( 1 : stm s1, @boxed 2:stm* s2 = None, 3: (1: int, 2: int))";
+        assert_debug_snapshot_matches!("parse_tags", constructor(asdl));
+    }
+
+    #[test]
+    fn parse_tag_errors() {
+        let asdl = r"
+            stm = Compound = x (stm s1) | Single = 2
+            expr = (1 expr e)
+            name = Name(99999999999: identifier)
+            ";
+        assert_parse_errors(
+            asdl,
+            &[
+                "2:30: Invalid tag, expected tag number",
+                "3:23: Missing ':' after field tag, expected ':'",
+                "4:25: Invalid tag, expected tag number",
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_extern_type() {
+        let asdl = "-- host type\n@boxed extern span( rust = \"TextRange\" ,python=\"Span\" )";
+        assert_debug_snapshot_matches!("parse_extern_type", ty(asdl, &mut vec![]));
+    }
+
+    #[test]
+    fn parse_field_constraints() {
+        let asdl = r"Op(int( 0..255 ) code, string(/[a-z]\/+/)? name, int(> -1)* depths)";
+        assert_debug_snapshot_matches!("parse_field_constraints", constructor(asdl));
+    }
+
+    #[test]
+    fn parse_constraint_errors() {
+        let asdl = r#"
+            stm = Op(int(0..) code)
+            expr = (int(>=0 depth)
+            name = Name(string(/[a-z]+) id)
+            big = (int(> 9223372036854775807) a)
+            small = (int(<-9223372036854775808) b, int(<-9223372036854775807) c)
+            "#;
+        assert_parse_errors(
+            asdl,
+            &[
+                "2:26: Invalid constraint, expected range or '>=' or '<=' or '>' or '<' or pattern",
+                "3:29: Unterminated constraint, expected ')'",
+                "4:44: Unterminated pattern, expected '/'\nnote: Invalid constraint",
+                "5:26: Bound admits no value\nnote: Invalid constraint",
+                "6:27: Bound admits no value\nnote: Invalid constraint",
+            ],
+        );
+    }
+
+    fn assert_parse_errors(txt: &str, errors: &[&str]) {
+        let errors: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        let actual: Vec<_> = parse(txt).1.iter().map(ToString::to_string).collect();
+        assert_eq!(errors, actual);
+    }
+
     fn assert_error<'a, T: Debug>(
         f: fn(&'a str) -> ParseResult<'a, T>,
         txt: &'a str,
         error_msg: &'a str,
     ) {
         let res = f(txt);
-        if let Err(Err::Error(err)) = res {
-            let v = convert_error(txt, err);
+        if let Err(Err::Error(err)) | Err(Err::Failure(err)) = res {
+            let v = error(txt, Err::Error(err)).to_string();
             assert_eq_text!(error_msg, v)
         } else {
-            assert!(false, "Expected error but get result:  {:?}", res);
+            panic!("Expected error but get result:  {:?}", res);
         }
     }
 }
@@ -0,0 +1,825 @@
+use std::fmt;
+
+use crate::model::*;
+use crate::util::{FieldNames, TUPLE_TYPE_ID};
+
+/// Column of the `|` separating constructors of a sum type which does not
+/// fit on one line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BarAlignment {
+    /// Under the `=` following the type id.
+    Equals,
+    /// Indented by the given number of spaces relative to the type id.
+    Indent(usize),
+}
+
+/// Where comments of types and constructors are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommentPlacement {
+    /// On separate lines above the commented item.
+    Above,
+    /// Comments are dropped.
+    Omit,
+}
+
+/// Formatting options of `Asdl::to_asdl_string_with`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrintOptions {
+    pub bar_alignment: BarAlignment,
+    /// Lines longer than this are broken between constructors and fields.
+    pub line_width: usize,
+    pub comments: CommentPlacement,
+    /// Indentation of type declarations inside a module.
+    pub indent: usize,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        PrintOptions {
+            bar_alignment: BarAlignment::Equals,
+            line_width: 100,
+            comments: CommentPlacement::Above,
+            indent: 4,
+        }
+    }
+}
+
+impl Asdl {
+    /// Prints the schema in canonical formatting. Parsing the output gives
+    /// back the same model, apart from spans.
+    pub fn to_asdl_string(&self) -> String {
+        self.to_asdl_string_with(&PrintOptions::default())
+    }
+
+    pub fn to_asdl_string_with(&self, options: &PrintOptions) -> String {
+        Printer { options, out: String::new() }.asdl(self)
+    }
+}
+
+impl fmt::Display for Asdl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_asdl_string())
+    }
+}
+
+/// Prints the constraint without the parentheses. Bounds are printed
+/// inclusive, `>0` reads back as `>=1`. A range without bounds restricts
+/// nothing and prints as nothing.
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constraint::Range { min: Some(min), max: Some(max) } => write!(f, "{}..{}", min, max),
+            Constraint::Range { min: Some(min), max: None } => write!(f, ">={}", min),
+            Constraint::Range { min: None, max: Some(max) } => write!(f, "<={}", max),
+            Constraint::Range { min: None, max: None } => Ok(()),
+            Constraint::Pattern(regex) => write!(f, "/{}/", regex),
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Literal::Int(n) => write!(f, "{}", n),
+            Literal::Bool(b) => write!(f, "{}", b),
+            Literal::String(s) => write!(f, "\"{}\"", s),
+            Literal::None => f.write_str("None"),
+            Literal::Constructor(id) => f.write_str(id),
+        }
+    }
+}
+
+struct Printer<'a> {
+    options: &'a PrintOptions,
+    out: String,
+}
+
+impl<'a> Printer<'a> {
+    fn asdl(mut self, asdl: &Asdl) -> String {
+        if !self.leading(&asdl.comments).is_empty() {
+            self.comments(&asdl.comments, 0);
+            self.out.push('\n');
+        }
+        for import in asdl.imports.iter() {
+            self.out.push_str(&format!("import \"{}\"\n", import.path));
+        }
+        if !asdl.imports.is_empty() && (asdl.module.is_some() || !asdl.types.is_empty()) {
+            self.out.push('\n');
+        }
+        let indent = match &asdl.module {
+            Some(module) => {
+                self.out.push_str(&format!("module {}", module.id));
+                if let Some(version) = &module.version {
+                    self.out.push_str(&format!(" version \"{}\"", version));
+                }
+                self.out.push_str(" {\n");
+                self.options.indent
+            }
+            None => 0,
+        };
+        let mut prev_compact = None;
+        for ty in asdl.types.iter() {
+            let start = self.out.len();
+            self.ty(ty, indent);
+            let compact = self.out[start..].lines().count() == 1;
+            if prev_compact.is_some() && !(prev_compact == Some(true) && compact) {
+                self.out.insert(start, '\n');
+            }
+            prev_compact = Some(compact);
+        }
+        if let Some(module) = &asdl.module {
+            self.comment_lines(&module.comments, indent);
+            self.out.push_str("}\n");
+        }
+        self.comment_lines(&asdl.comments, 0);
+        self.out
+    }
+
+    fn ty(&mut self, ty: &Type, indent: usize) {
+        let comments = match ty {
+            Type::ProdType(ty) => {
+                self.prod_type(ty, indent);
+                &ty.comments
+            }
+            Type::SumType(ty) => {
+                self.sum_type(ty, indent);
+                &ty.comments
+            }
+            Type::AliasType(ty) => {
+                self.alias_type(ty, indent);
+                &ty.comments
+            }
+            Type::ExternType(ty) => {
+                self.extern_type(ty, indent);
+                &ty.comments
+            }
+        };
+        let trailing = self.trailing(comments, &" ".repeat(indent));
+        if !trailing.is_empty() {
+            self.out.pop();
+            self.out.push_str(&trailing);
+            self.out.push('\n');
+        }
+    }
+
+    fn extern_type(&mut self, ty: &ExternType, indent: usize) {
+        self.comments(&ty.comments, indent);
+        self.annotations(&ty.annotations, indent);
+        self.out.push_str(&format!("{}extern {}", " ".repeat(indent), ty.id));
+        if !ty.mappings.is_empty() {
+            let mappings: Vec<_> =
+                ty.mappings.iter().map(|m| format!("{} = \"{}\"", m.language, m.target)).collect();
+            self.out.push_str(&format!("({})", mappings.join(", ")));
+        }
+        self.out.push('\n');
+    }
+
+    fn alias_type(&mut self, ty: &AliasType, indent: usize) {
+        self.comments(&ty.comments, indent);
+        self.annotations(&ty.annotations, indent);
+        self.out.push_str(&format!("{}{} = {}\n", " ".repeat(indent), ty.id, ty.target));
+    }
+
+    fn prod_type(&mut self, ty: &ProdType, indent: usize) {
+        self.comments(&ty.comments, indent);
+        self.annotations(&ty.annotations, indent);
+        let head = format!("{}{} = ", " ".repeat(indent), declaration(&ty.id, &ty.params));
+        if ty.attributes.is_empty() {
+            let fields = self.fields(&ty.fields, Some(width(&head)));
+            self.out.push_str(&format!("{}{}\n", head, fields));
+            return;
+        }
+        let attributes = self.fields(&ty.attributes, None);
+        let line = format!("{}{} attributes {}", head, self.fields(&ty.fields, None), attributes);
+        let has_comments = self.has_comments(&ty.fields) || self.has_comments(&ty.attributes);
+        if !has_comments && width(&line) <= self.options.line_width {
+            self.out.push_str(&line);
+            self.out.push('\n');
+            return;
+        }
+
+        let column = match self.options.bar_alignment {
+            BarAlignment::Equals => width(&head) - 2,
+            BarAlignment::Indent(n) => indent + n,
+        };
+        let fields = self.fields(&ty.fields, Some(width(&head)));
+        self.out.push_str(&format!("{}{}\n", head, fields));
+        let attributes = self.fields(&ty.attributes, Some(column + "attributes ".len()));
+        self.out.push_str(&format!("{}attributes {}\n", " ".repeat(column), attributes));
+    }
+
+    fn sum_type(&mut self, ty: &SumType, indent: usize) {
+        self.comments(&ty.comments, indent);
+        self.annotations(&ty.annotations, indent);
+        let head = format!("{}{} =", " ".repeat(indent), declaration(&ty.id, &ty.params));
+        let attributes = if ty.attributes.is_empty() {
+            None
+        } else {
+            Some(format!("attributes {}", self.fields(&ty.attributes, None)))
+        };
+        let constructors: Vec<_> =
+            ty.constructors.iter().map(|c| self.constructor(c, None)).collect();
+        let mut line = format!("{} {}", head, constructors.join(" | "));
+        if let Some(attributes) = &attributes {
+            line = format!("{} {}", line, attributes);
+        }
+        let bar = match self.options.bar_alignment {
+            BarAlignment::Equals => width(&head) - 1,
+            BarAlignment::Indent(n) => indent + n,
+        };
+        let pad = " ".repeat(bar);
+        // Trailing comments of the last constructor can end any layout.
+        let count = ty.constructors.len();
+        let has_comments = ty.constructors.iter().enumerate().any(|(n, c)| {
+            !self.leading(&c.comments).is_empty()
+                || (n + 1 < count && !self.trailing(&c.comments, &pad).is_empty())
+        });
+        let last_trailing =
+            ty.constructors.last().map(|c| self.trailing(&c.comments, &pad)).unwrap_or_default();
+        let has_field_comments = ty.constructors.iter().any(|c| self.has_comments(&c.fields))
+            || self.has_comments(&ty.attributes);
+        let fits = width(&line) <= self.options.line_width
+            && (attributes.is_none() || last_trailing.is_empty())
+            && !last_trailing.contains('\n');
+        if !has_comments && !has_field_comments && fits {
+            self.out.push_str(&line);
+            self.out.push_str(&last_trailing);
+            self.out.push('\n');
+            return;
+        }
+
+        if has_comments {
+            // Comments may only follow the `|`, so it goes at the end of lines.
+            self.out.push_str(&head);
+            self.out.push('\n');
+            for (n, constr) in ty.constructors.iter().enumerate() {
+                self.comments(&constr.comments, bar);
+                let text = self.constructor(constr, Some(bar));
+                let sep = if n + 1 < count { " |" } else { "" };
+                let trailing = self.trailing(&constr.comments, &pad);
+                self.out.push_str(&format!("{}{}{}{}\n", pad, text, sep, trailing));
+            }
+        } else {
+            for (n, constr) in ty.constructors.iter().enumerate() {
+                if n == 0 {
+                    let text = self.constructor(constr, Some(width(&head) + 1));
+                    self.out.push_str(&format!("{} {}", head, text));
+                } else {
+                    let text = self.constructor(constr, Some(bar + 2));
+                    self.out.push_str(&format!("{}| {}", pad, text));
+                }
+                if n + 1 == count {
+                    self.out.push_str(&last_trailing);
+                }
+                self.out.push('\n');
+            }
+        }
+        if !ty.attributes.is_empty() {
+            let fields = self.fields(&ty.attributes, Some(bar + "attributes ".len()));
+            self.out.push_str(&format!("{}attributes {}\n", pad, fields));
+        }
+    }
+
+    /// Prints a constructor starting at `column`, or on one line if the
+    /// column is not known.
+    fn constructor(&self, constr: &Constructor, column: Option<usize>) -> String {
+        let mut head = format!("{}{}", annotations(&constr.annotations), constr.id);
+        if let Some(tag) = constr.tag {
+            head.push_str(&format!(" = {}", tag));
+            if !constr.fields.is_empty() {
+                head.push(' ');
+            }
+        }
+        if constr.fields.is_empty() {
+            head
+        } else {
+            let fields = self.fields(&constr.fields, column.map(|c| c + width(&head)));
+            format!("{}{}", head, fields)
+        }
+    }
+
+    /// Prints a field list starting at `column`, one field per line if it
+    /// does not fit into the line width or if fields have comments. The `)`
+    /// goes on a line of its own after trailing comments of the last field.
+    fn fields(&self, fields: &[Field], column: Option<usize>) -> String {
+        let mut names = FieldNames::default();
+        let texts: Vec<_> = fields.iter().map(|f| field(f, &mut names)).collect();
+        let line = format!("({})", texts.join(", "));
+        let column = match column {
+            Some(column) if self.has_comments(fields) => column,
+            Some(column) if fields.len() > 1 && column + width(&line) > self.options.line_width => {
+                column
+            }
+            _ => return line,
+        };
+        let pad = " ".repeat(column + 1);
+        let mut lines = vec![];
+        let mut trailing = String::new();
+        for (n, (f, text)) in fields.iter().zip(texts).enumerate() {
+            let mut line = String::new();
+            for c in self.leading(&f.comments) {
+                line.push_str(&format!("{}\n{}", comment(c), pad));
+            }
+            line.push_str(&text);
+            if n + 1 < fields.len() {
+                line.push(',');
+            }
+            trailing = self.trailing(&f.comments, &pad);
+            line.push_str(&trailing);
+            lines.push(line);
+        }
+        let close =
+            if trailing.is_empty() { String::new() } else { format!("\n{}", " ".repeat(column)) };
+        format!("({}{})", lines.join(&format!("\n{}", pad)), close)
+    }
+
+    fn has_comments(&self, fields: &[Field]) -> bool {
+        self.options.comments == CommentPlacement::Above
+            && fields.iter().any(|f| !f.comments.is_empty())
+    }
+
+    /// Comments of a declaration which go above it.
+    fn leading<'c>(&self, comments: &'c [Comment]) -> Vec<&'c Comment> {
+        match self.options.comments {
+            CommentPlacement::Above => comments.iter().filter(|c| !c.trailing).collect(),
+            CommentPlacement::Omit => vec![],
+        }
+    }
+
+    /// Prints the comments following a declaration. The first one goes on
+    /// the line of the declaration, as does any after a block comment, the
+    /// other ones on lines of their own starting with `pad`. Doc comments
+    /// always start a line, on the line of the declaration they would
+    /// document the next one.
+    fn trailing(&self, comments: &[Comment], pad: &str) -> String {
+        let mut out = String::new();
+        if self.options.comments == CommentPlacement::Omit {
+            return out;
+        }
+        let mut same_line = true;
+        for c in comments.iter().filter(|c| c.trailing) {
+            if same_line && c.kind != CommentKind::Doc {
+                out.push(' ');
+            } else {
+                out.push_str(&format!("\n{}", pad));
+            }
+            out.push_str(&comment(c));
+            same_line = c.kind == CommentKind::Block;
+        }
+        out
+    }
+
+    /// Prints annotations of a type on a line above it.
+    fn annotations(&mut self, annotations: &[Annotation], indent: usize) {
+        if !annotations.is_empty() {
+            let line = self::annotations(annotations);
+            self.out.push_str(&format!("{}{}\n", " ".repeat(indent), line.trim_end()));
+        }
+    }
+
+    /// Prints the comments above a declaration.
+    fn comments(&mut self, comments: &[Comment], indent: usize) {
+        for c in self.leading(comments) {
+            self.out.push_str(&format!("{}{}\n", " ".repeat(indent), comment(c)));
+        }
+    }
+
+    /// Prints trailing comments on lines of their own, the comments before
+    /// the `}` of the module or at the end of the file.
+    fn comment_lines(&mut self, comments: &[Comment], indent: usize) {
+        if self.options.comments == CommentPlacement::Omit {
+            return;
+        }
+        for c in comments.iter().filter(|c| c.trailing) {
+            self.out.push_str(&format!("{}{}\n", " ".repeat(indent), comment(c)));
+        }
+    }
+}
+
+/// Prints a comment. Line comments use `//` and doc comments `///`.
+fn comment(comment: &Comment) -> String {
+    let marker = match comment.kind {
+        CommentKind::Doc => "///",
+        CommentKind::Line => "//",
+        CommentKind::Block => return format!("/* {} */", comment.text),
+    };
+    if comment.text.is_empty() {
+        marker.to_string()
+    } else {
+        format!("{} {}", marker, comment.text)
+    }
+}
+
+/// Prints a field. The name is left out if parsing would generate the
+/// same one.
+fn field(field: &Field, names: &mut FieldNames) -> String {
+    let mut ty = type_ref(field, &field.arity);
+    if let Some(tag) = field.tag {
+        ty = format!("{}: {}", tag, ty);
+    }
+    let annotations = annotations(&field.annotations);
+    let name_type_id = if field.tuple.is_some() { TUPLE_TYPE_ID } else { &field.type_id };
+    let default = match &field.default {
+        Some(value) => format!(" = {}", value),
+        None => String::new(),
+    };
+    if names.peek(name_type_id) == field.id {
+        names.generate(name_type_id);
+        format!("{}{}{}", annotations, ty, default)
+    } else {
+        format!("{}{} {}{}", annotations, ty, field.id, default)
+    }
+}
+
+/// Prints a field type such as `expr?*`, `{identifier: expr}` or
+/// `(expr, expr)*`.
+fn type_ref(field: &Field, arity: &Arity) -> String {
+    let modifier = match arity {
+        Arity::Required => return value_type(field),
+        Arity::Optional(_) => "?",
+        Arity::Repeated(_) => "*",
+        Arity::NonEmpty(_) => "+",
+        Arity::Map { key, value } => return format!("{{{}: {}}}", key, type_ref(field, value)),
+    };
+    format!("{}{}", type_ref(field, arity.inner().unwrap()), modifier)
+}
+
+fn value_type(field: &Field) -> String {
+    match &field.tuple {
+        Some(elements) => {
+            let mut names = FieldNames::default();
+            let elements: Vec<_> = elements.iter().map(|f| self::field(f, &mut names)).collect();
+            format!("({})", elements.join(", "))
+        }
+        None => {
+            let constraint = match &field.constraint {
+                Some(Constraint::Range { min: None, max: None }) | None => String::new(),
+                Some(constraint) => format!("({})", constraint),
+            };
+            format!("{}{}{}", field.type_id, type_args(&field.args), constraint)
+        }
+    }
+}
+
+/// Prints the name of a declaration followed by its type parameters.
+fn declaration(id: &str, params: &[String]) -> String {
+    if params.is_empty() {
+        id.to_string()
+    } else {
+        format!("{}<{}>", id, params.join(", "))
+    }
+}
+
+fn type_args(args: &[TypeArg]) -> String {
+    if args.is_empty() {
+        return String::new();
+    }
+    let args: Vec<_> =
+        args.iter().map(|a| format!("{}{}", a.type_id, type_args(&a.args))).collect();
+    format!("<{}>", args.join(", "))
+}
+
+/// Prints annotations, each followed by a space. Values are quoted unless
+/// they read back the same without quotes.
+fn annotations(annotations: &[Annotation]) -> String {
+    let mut out = String::new();
+    for annotation in annotations {
+        out.push('@');
+        out.push_str(&annotation.name);
+        match &annotation.value {
+            Some(value) if is_raw_value(value) => out.push_str(&format!("({})", value)),
+            Some(value) => out.push_str(&format!("(\"{}\")", value)),
+            None => {}
+        }
+        out.push(' ');
+    }
+    out
+}
+
+fn is_raw_value(value: &str) -> bool {
+    !value.is_empty() && value.trim() == value && !value.starts_with('"') && !value.contains(')')
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Debug output of `asdl` without spans, to compare models parsed from
+    /// differently formatted sources.
+    fn without_spans(asdl: &Asdl) -> String {
+        let debug = format!("{:?}", asdl);
+        let mut parts = debug.split("Span(");
+        let mut stripped = parts.next().unwrap_or_default().to_string();
+        for part in parts {
+            stripped.push_str(&part[part.find(')').map_or(0, |end| end + 1)..]);
+        }
+        stripped
+    }
+
+    /// Text of every comment in `src`, without comment markers.
+    fn comment_texts(src: &str) -> Vec<String> {
+        let root = crate::SyntaxNode::parse(src).unwrap();
+        let markers: &[_] = &['/', '-', '|', '*'];
+        root.tokens()
+            .into_iter()
+            .filter(|t| t.kind == crate::SyntaxKind::Comment)
+            .map(|t| t.text.trim_matches(markers).trim().to_string())
+            .collect()
+    }
+
+    /// Formats `src` and checks that no comment is lost and that formatting
+    /// the output again does not change it.
+    fn format(src: &str) -> String {
+        let text = Asdl::parse(src).unwrap().to_asdl_string();
+        assert_eq!(comment_texts(&text), comment_texts(src));
+        assert_eq!(Asdl::parse(&text).unwrap().to_asdl_string(), text);
+        text
+    }
+
+    const SCHEMA: &str = r#"
+// Root comment
+
+import "base.asdl"
+module Simple version "1.0" {
+    // Statements
+    stm = FunctionDef(identifier name, arguments args, stm* body, expr* decorator_list, expr? returns)
+        | Return(expr? value) | Pass
+        attributes (int lineno, int col_offset)
+
+    expr = Name(identifier id) | Num(int n)
+    arguments = (identifier* args, identifier? vararg)
+    arg = (identifier arg, expr? annotation) attributes (int lineno, int col_offset)
+    slice =
+        // Whole range
+        Slice(expr? lower, expr? upper) |
+        // Single index
+        Index(expr value)
+}
+"#;
+
+    #[test]
+    fn print_default() {
+        let asdl = Asdl::parse(SCHEMA).unwrap();
+        let text = asdl.to_asdl_string();
+        assert_eq!(
+            text,
+            r#"// Root comment
+
+import "base.asdl"
+
+module Simple version "1.0" {
+    // Statements
+    stm = FunctionDef(identifier name,
+                      arguments args,
+                      stm* body,
+                      expr* decorator_list,
+                      expr? returns)
+        | Return(expr? value)
+        | Pass
+        attributes (int lineno, int col_offset)
+
+    expr = Name(identifier id) | Num(int n)
+    arguments = (identifier* args, identifier? vararg)
+    arg = (identifier arg, expr? annotation) attributes (int lineno, int col_offset)
+
+    slice =
+          // Whole range
+          Slice(expr? lower, expr? upper) |
+          // Single index
+          Index(expr value)
+}
+"#
+        );
+        assert_eq!(without_spans(&Asdl::parse(&text).unwrap()), without_spans(&asdl));
+        assert_eq!(asdl.to_string(), text);
+    }
+
+    #[test]
+    fn print_with_options() {
+        let asdl = Asdl::parse(SCHEMA).unwrap();
+        let options = PrintOptions {
+            bar_alignment: BarAlignment::Indent(2),
+            line_width: 40,
+            comments: CommentPlacement::Omit,
+            indent: 2,
+        };
+        let text = asdl.to_asdl_string_with(&options);
+        assert_eq!(
+            text,
+            r#"import "base.asdl"
+
+module Simple version "1.0" {
+  stm = FunctionDef(identifier name,
+                    arguments args,
+                    stm* body,
+                    expr* decorator_list,
+                    expr? returns)
+    | Return(expr? value)
+    | Pass
+    attributes (int lineno,
+                int col_offset)
+
+  expr = Name(identifier id)
+    | Num(int n)
+
+  arguments = (identifier* args,
+               identifier? vararg)
+
+  arg = (identifier arg,
+         expr? annotation)
+    attributes (int lineno,
+                int col_offset)
+
+  slice = Slice(expr? lower,
+                expr? upper)
+    | Index(expr value)
+}
+"#
+        );
+        assert_eq!(Asdl::parse(&text).unwrap().to_asdl_string_with(&options), text);
+    }
+
+    #[test]
+    fn print_annotations() {
+        let asdl = r#"
+            // stm
+            @derive(Clone, Debug)   @boxed
+            stm = @rename( "If_" ) If(@boxed expr test, @doc("a) b") int n) | Pass
+            "#;
+        let asdl = Asdl::parse(asdl).unwrap();
+        assert_eq!(
+            asdl.to_asdl_string(),
+            r#"// stm
+@derive(Clone, Debug) @boxed
+stm = @rename(If_) If(@boxed expr test, @doc("a) b") int n) | Pass
+"#
+        );
+        assert_eq!(
+            without_spans(&Asdl::parse(&asdl.to_asdl_string()).unwrap()),
+            without_spans(&asdl)
+        );
+    }
+
+    #[test]
+    fn print_tags() {
+        let asdl = "stm = @boxed Compound=3(1:stm s1, 2 : stm* s2 = None) | Pass = 7";
+        let asdl = Asdl::parse(asdl).unwrap();
+        assert_eq!(
+            asdl.to_asdl_string(),
+            "stm = @boxed Compound = 3 (1: stm s1, 2: stm* s2 = None) | Pass = 7\n"
+        );
+        assert_eq!(
+            without_spans(&Asdl::parse(&asdl.to_asdl_string()).unwrap()),
+            without_spans(&asdl)
+        );
+    }
+
+    #[test]
+    fn print_constraints() {
+        let asdl = r"op = (int(>0) a, int(<= -1)* b, int( 0..255 ) c, string(/[a-z]\//)? d)";
+        let mut asdl = Asdl::parse(asdl).unwrap();
+        assert_eq!(
+            asdl.to_asdl_string(),
+            "op = (int(>=1) a, int(<=-1)* b, int(0..255) c, string(/[a-z]\\//)? d)\n"
+        );
+        if let Type::ProdType(pty) = &mut asdl.types[0] {
+            pty.fields[0].constraint = Some(Constraint::Range { min: None, max: None });
+        }
+        let text = asdl.to_asdl_string();
+        assert!(text.starts_with("op = (int a, "), "{}", text);
+        Asdl::parse(&text).unwrap();
+    }
+
+    #[test]
+    fn print_field_comments() {
+        let asdl = r#"
+            stm = Compound(// the body
+                           stm s1, stm* s2) | Pass
+            arg = (identifier arg) attributes (// line
+                int lineno)
+            "#;
+        let asdl = Asdl::parse(asdl).unwrap();
+        assert_eq!(
+            asdl.to_asdl_string(),
+            r#"stm = Compound(// the body
+               stm s1,
+               stm* s2)
+    | Pass
+
+arg = (identifier arg)
+    attributes (// line
+                int lineno)
+"#
+        );
+        assert_eq!(
+            without_spans(&Asdl::parse(&asdl.to_asdl_string()).unwrap()),
+            without_spans(&asdl)
+        );
+        let options = PrintOptions { comments: CommentPlacement::Omit, ..PrintOptions::default() };
+        assert_eq!(
+            asdl.to_asdl_string_with(&options),
+            "stm = Compound(stm s1, stm* s2) | Pass\n\
+             arg = (identifier arg) attributes (int lineno)\n"
+        );
+    }
+
+    #[test]
+    fn print_comment_kinds() {
+        let asdl = "--| Doc\n-- Note\n/* Block */\nstm = Pass -- Break\n    | /// Doc\n Break";
+        let asdl = Asdl::parse(asdl).unwrap();
+        assert_eq!(
+            asdl.to_asdl_string(),
+            "/// Doc\n// Note\n/* Block */\nstm =\n    Pass | // Break\n    /// Doc\n    Break\n"
+        );
+        assert_eq!(
+            without_spans(&Asdl::parse(&asdl.to_asdl_string()).unwrap()),
+            without_spans(&asdl)
+        );
+    }
+
+    #[test]
+    fn print_without_module() {
+        let asdl = Asdl::parse("// stm\nstm = Single\nexpr = (stm, stm s, stm)").unwrap();
+        assert_eq!(asdl.to_asdl_string(), "// stm\nstm = Single\n\nexpr = (stm, stm s, stm)\n");
+        assert_eq!(
+            without_spans(&Asdl::parse(&asdl.to_asdl_string()).unwrap()),
+            without_spans(&asdl)
+        );
+    }
+
+    #[test]
+    fn print_trailing_comments() {
+        let src = "module M {\n  -- lead\n  t = A | B -- trailing\n  -- before close\n}\n-- eof\n";
+        assert_eq!(
+            format(src),
+            "module M {\n    // lead\n    t = A | B // trailing\n    // before close\n}\n// eof\n"
+        );
+        let src = r"
+-- header
+
+stm = Compound(stm s1, -- first
+               stm* s2 -- second
+               -- after s2
+               ) -- compound
+    | Pass -- pass
+    -- before attributes
+    attributes (int line) -- end of stm
+pair = ((int, -- x
+         int) p, int q) -- pair
+other = (int a
+    -- before close
+)
+ident = string /* alias */ -- ident
+extern span -- extern
+-- eof 1
+
+-- eof 2
+";
+        assert_eq!(
+            format(src),
+            r"// header
+
+stm =
+    Compound(stm s1, // first
+             stm* s2 // second
+             // after s2
+            ) | // compound
+    Pass // pass
+    // before attributes
+    attributes (int line) // end of stm
+
+pair = ((int, int) p, // x
+        int q) // pair
+
+other = (int a // before close
+        )
+
+ident = string /* alias */ // ident
+extern span // extern
+// eof 1
+// eof 2
+"
+        );
+        let options = PrintOptions { comments: CommentPlacement::Omit, ..PrintOptions::default() };
+        let asdl = Asdl::parse(src).unwrap();
+        assert_eq!(
+            asdl.to_asdl_string_with(&options),
+            "stm = Compound(stm s1, stm* s2) | Pass attributes (int line)\n\
+             pair = ((int, int) p, int q)\n\
+             other = (int a)\n\
+             ident = string\n\
+             extern span\n"
+        );
+    }
+
+    #[test]
+    fn format_keeps_comments() {
+        for src in [SCHEMA, "-- only\n", "t = A -- a\n | /// b\n B", "t = (int a, /* x */ int b)"] {
+            format(src);
+        }
+    }
+}
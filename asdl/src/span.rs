@@ -0,0 +1,91 @@
+use std::fmt;
+use std::ops::Range;
+
+/// Position in the source text. `line` and `column` are 1-based,
+/// `column` is counted in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Location {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Region of the source text a model node was parsed from.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+    /// Index of the source file in `Asdl::files`. It is 0 for schemas
+    /// parsed from a string.
+    pub file: usize,
+}
+
+impl Span {
+    pub fn new(start: Location, end: Location) -> Self {
+        Span { start, end, file: 0 }
+    }
+
+    /// Byte range of the span in the source text.
+    pub fn range(&self) -> Range<usize> {
+        self.start.offset..self.end.offset
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Span({}..{}, {}:{}-{}:{}",
+            self.start.offset,
+            self.end.offset,
+            self.start.line,
+            self.start.column,
+            self.end.line,
+            self.end.column
+        )?;
+        if self.file != 0 {
+            write!(f, ", file {}", self.file)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.start.line, self.start.column)
+    }
+}
+
+/// Translates slices of the parsed source into spans.
+pub(crate) struct SourceMap<'a> {
+    src: &'a str,
+    file: usize,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> SourceMap<'a> {
+    pub(crate) fn new(src: &'a str, file: usize) -> Self {
+        let line_starts =
+            std::iter::once(0).chain(src.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        SourceMap { src, file, line_starts }
+    }
+
+    pub(crate) fn location(&self, offset: usize) -> Location {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        let line_start = self.line_starts[line];
+        let column = self.src[line_start..offset].chars().count() + 1;
+        Location { offset, line: line + 1, column }
+    }
+
+    /// Span of `text`, which must be a subslice of the source.
+    pub(crate) fn span(&self, text: &str) -> Span {
+        let start = text.as_ptr() as usize - self.src.as_ptr() as usize;
+        Span {
+            file: self.file,
+            ..Span::new(self.location(start), self.location(start + text.len()))
+        }
+    }
+}
@@ -1,8 +1,10 @@
-use std::fmt::Write;
 use std::collections::HashMap;
 
 use crate::ast;
 
+/// Unnamed fields of inline tuple types are named after this type id.
+pub(crate) const TUPLE_TYPE_ID: &str = "tuple";
+
 #[derive(Default)]
 pub(crate) struct FieldNames {
     names_indexes: HashMap<String, u32>,
@@ -16,19 +18,21 @@ impl FieldNames {
     ) -> String {
         match id {
             Option::Some(id) => id.to_string(),
-            Option::None => {
-                let type_id = type_id.to_string();
-                let index = self.names_indexes.entry(type_id.to_string()).or_insert(0);
-                let res = if *index == 0 {
-                    type_id.to_string()
-                } else {
-                    let mut buf = String::new();
-                    write!(&mut buf, "{}{}", type_id, index).unwrap();
-                    buf
-                };
-                *index += 1;
-                res
-            }
+            Option::None => self.generate(type_id.0),
+        }
+    }
+
+    /// Name which `generate` returns next for `type_id`.
+    pub(crate) fn peek(&self, type_id: &str) -> String {
+        match self.names_indexes.get(type_id) {
+            None | Some(0) => type_id.to_string(),
+            Some(index) => format!("{}{}", type_id, index),
         }
     }
+
+    pub(crate) fn generate(&mut self, type_id: &str) -> String {
+        let res = self.peek(type_id);
+        *self.names_indexes.entry(type_id.to_string()).or_insert(0) += 1;
+        res
+    }
 }
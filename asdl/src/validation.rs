@@ -0,0 +1,761 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+use regex::Regex;
+
+use crate::model::*;
+use crate::span::Span;
+
+/// Semantic problem found in a successfully parsed schema.
+///
+/// `span` points to the offending declaration, `first` to the earlier
+/// declaration it conflicts with.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum ValidationError {
+    /// A field references a type which is neither declared nor builtin.
+    UndefinedType { type_id: String, field: String, span: Span },
+    /// A type reference passes a different number of type arguments than the
+    /// referenced type has parameters. `used_in` is the field or alias.
+    WrongTypeArgCount {
+        type_id: String,
+        expected: usize,
+        found: usize,
+        used_in: String,
+        span: Span,
+    },
+    /// An alias wraps a type which is neither declared nor builtin.
+    UndefinedTarget { type_id: String, alias: String, span: Span },
+    /// An alias wraps itself, directly or through other aliases.
+    CyclicAlias { alias: String, span: Span },
+    /// A constraint restricts a type which does not take it: ranges need
+    /// `int`, patterns `string` or `identifier`. `expected` names them.
+    InvalidConstraint { type_id: String, expected: String, field: String, span: Span },
+    /// The pattern of a constraint is not a valid regular expression.
+    InvalidPattern { pattern: String, field: String, span: Span },
+    /// The lower bound of a range constraint is above the upper bound.
+    EmptyRange { field: String, span: Span },
+    /// A default value does not fit the type of its field.
+    InvalidDefault { default: String, field: String, span: Span },
+    /// The same type name is declared more than once.
+    DuplicateType { type_id: String, span: Span, first: Span },
+    /// A constructor name is used more than once. Constructors share one namespace.
+    DuplicateConstructor {
+        id: String,
+        type_id: String,
+        first_type_id: String,
+        span: Span,
+        first: Span,
+    },
+    /// An extern type names the same target language more than once.
+    DuplicateMapping { language: String, type_id: String, span: Span, first: Span },
+    /// Two constructors of a sum type, or two fields or attributes of the
+    /// same node, have the same tag.
+    DuplicateTag { tag: u32, owner: String, span: Span, first: Span },
+    /// Two fields or attributes of the same node end up with the same name.
+    DuplicateField { id: String, owner: String, span: Span, first: Span },
+    /// A field of a generic type passes ever larger type arguments to
+    /// `type_id`, as in `t<T> = (t<t<T>>? x)`. Monomorphization would not end.
+    InfiniteInstantiation { type_id: String, field: String, span: Span },
+    /// Monomorphization names the concrete type or constructor `instance` like
+    /// `other`, which is another one. `first` is where `other` is declared or
+    /// first instantiated.
+    InstanceClash { name: String, instance: String, other: String, span: Span, first: Span },
+    /// A tag written in the schema is recorded in the lock file for `other`,
+    /// which has or had it, possibly as a deleted item.
+    ReservedTag { tag: u32, path: String, other: String, span: Span },
+}
+
+impl ValidationError {
+    pub fn span(&self) -> Span {
+        match self {
+            ValidationError::UndefinedType { span, .. }
+            | ValidationError::WrongTypeArgCount { span, .. }
+            | ValidationError::UndefinedTarget { span, .. }
+            | ValidationError::CyclicAlias { span, .. }
+            | ValidationError::InvalidConstraint { span, .. }
+            | ValidationError::InvalidPattern { span, .. }
+            | ValidationError::EmptyRange { span, .. }
+            | ValidationError::InvalidDefault { span, .. }
+            | ValidationError::DuplicateType { span, .. }
+            | ValidationError::DuplicateConstructor { span, .. }
+            | ValidationError::DuplicateMapping { span, .. }
+            | ValidationError::DuplicateTag { span, .. }
+            | ValidationError::DuplicateField { span, .. }
+            | ValidationError::InfiniteInstantiation { span, .. }
+            | ValidationError::InstanceClash { span, .. }
+            | ValidationError::ReservedTag { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::UndefinedType { type_id, field, .. } => {
+                write!(f, "Undefined type `{}` referenced by field `{}`", type_id, field)
+            }
+            ValidationError::WrongTypeArgCount { type_id, expected, found, used_in, .. } => {
+                let plural = if *expected == 1 { "" } else { "s" };
+                write!(
+                    f,
+                    "Type `{}` takes {} type argument{}, found {} in `{}`",
+                    type_id, expected, plural, found, used_in
+                )
+            }
+            ValidationError::UndefinedTarget { type_id, alias, .. } => {
+                write!(f, "Undefined type `{}` referenced by alias `{}`", type_id, alias)
+            }
+            ValidationError::CyclicAlias { alias, .. } => {
+                write!(f, "Alias `{}` refers to itself", alias)
+            }
+            ValidationError::InvalidConstraint { type_id, expected, field, .. } => write!(
+                f,
+                "Constraint of field `{}` requires {}, found `{}`",
+                field, expected, type_id
+            ),
+            ValidationError::InvalidPattern { pattern, field, .. } => write!(
+                f,
+                "Pattern `/{}/` of field `{}` is not a valid regular expression",
+                pattern, field
+            ),
+            ValidationError::EmptyRange { field, .. } => {
+                write!(f, "Range of field `{}` admits no value", field)
+            }
+            ValidationError::InvalidDefault { default, field, .. } => {
+                write!(f, "Default value `{}` does not fit the type of field `{}`", default, field)
+            }
+            ValidationError::DuplicateType { type_id, .. } => {
+                write!(f, "Type `{}` is defined more than once", type_id)
+            }
+            ValidationError::DuplicateConstructor { id, type_id, first_type_id, .. } => {
+                if type_id == first_type_id {
+                    write!(f, "Constructor `{}` is defined more than once in `{}`", id, type_id)
+                } else {
+                    write!(
+                        f,
+                        "Constructor `{}` of `{}` is already defined in `{}`",
+                        id, type_id, first_type_id
+                    )
+                }
+            }
+            ValidationError::DuplicateMapping { language, type_id, .. } => {
+                write!(f, "Language `{}` is mapped more than once in `{}`", language, type_id)
+            }
+            ValidationError::DuplicateTag { tag, owner, .. } => {
+                write!(f, "Tag `{}` is used more than once in `{}`", tag, owner)
+            }
+            ValidationError::DuplicateField { id, owner, .. } => {
+                write!(f, "Field `{}` is defined more than once in `{}`", id, owner)
+            }
+            ValidationError::InfiniteInstantiation { type_id, field, .. } => write!(
+                f,
+                "Field `{}` instantiates `{}` with ever larger type arguments",
+                field, type_id
+            ),
+            ValidationError::InstanceClash { name, instance, other, .. } => write!(
+                f,
+                "Monomorphized name `{}` of `{}` is already used by `{}`",
+                name, instance, other
+            ),
+            ValidationError::ReservedTag { tag, path, other, .. } => {
+                write!(
+                    f,
+                    "Tag `{}` of `{}` is reserved for `{}` by the lock file",
+                    tag, path, other
+                )
+            }
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+impl Asdl {
+    /// Checks that every referenced type exists and that no type, constructor
+    /// or field name is declared twice. Returns all problems found.
+    pub fn validate(&self) -> std::result::Result<(), Vec<ValidationError>> {
+        let v = Validator { types: &self.types, index: self.index() };
+        let mut errors = Vec::new();
+        let mut type_ids = HashMap::new();
+        let mut constructors = HashMap::new();
+        for ty in self.types.iter() {
+            match type_ids.get(&ty.id()) {
+                Some(first) => errors.push(ValidationError::DuplicateType {
+                    type_id: ty.id().to_string(),
+                    span: ty.span(),
+                    first: *first,
+                }),
+                None => {
+                    type_ids.insert(ty.id(), ty.span());
+                }
+            }
+            match ty {
+                Type::SumType(sty) => {
+                    let tags = sty.constructors.iter().filter_map(|c| Some((c.tag?, c.span)));
+                    check_tags(&sty.id, HashMap::new(), tags, &mut errors);
+                    for c in sty.constructors.iter() {
+                        match constructors.get(&c.id) {
+                            Some((first_type_id, first)) => {
+                                errors.push(ValidationError::DuplicateConstructor {
+                                    id: c.id.clone(),
+                                    type_id: sty.id.clone(),
+                                    first_type_id: String::clone(first_type_id),
+                                    span: c.span,
+                                    first: *first,
+                                })
+                            }
+                            None => {
+                                constructors.insert(c.id.clone(), (sty.id.clone(), c.span));
+                            }
+                        }
+                        let owner = format!("{}.{}", sty.id, c.id);
+                        let (attributes, params) = (&sty.attributes, &sty.params);
+                        v.check_fields(&owner, &c.fields, attributes, params, &mut errors);
+                    }
+                    v.check_fields(&sty.id, &sty.attributes, &[], &sty.params, &mut errors);
+                }
+                Type::ProdType(pty) => {
+                    let (attributes, params) = (&pty.attributes, &pty.params);
+                    v.check_fields(&pty.id, &pty.fields, attributes, params, &mut errors);
+                    v.check_fields(&pty.id, &pty.attributes, &[], params, &mut errors);
+                }
+                Type::AliasType(aty) => v.check_alias(aty, &mut errors),
+                Type::ExternType(ety) => {
+                    let mut languages: HashMap<&str, Span> = HashMap::new();
+                    for m in ety.mappings.iter() {
+                        match languages.get(m.language.as_str()) {
+                            Some(first) => errors.push(ValidationError::DuplicateMapping {
+                                language: m.language.clone(),
+                                type_id: ety.id.clone(),
+                                span: m.span,
+                                first: *first,
+                            }),
+                            None => {
+                                languages.insert(&m.language, m.span);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        v.check_instantiations(&mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Checks of one schema, sharing an index of its types.
+struct Validator<'a> {
+    types: &'a [Type],
+    index: TypeIndex<'a>,
+}
+
+impl<'a> Validator<'a> {
+    /// Checks that the target of `alias` exists and does not lead back to it
+    /// through other aliases. Undefined targets are reported by the alias
+    /// which references them, cycles by every alias on the cycle.
+    fn check_alias(&self, alias: &AliasType, errors: &mut Vec<ValidationError>) {
+        match self.index.resolve(&alias.target) {
+            None => errors.push(ValidationError::UndefinedTarget {
+                type_id: alias.target.clone(),
+                alias: alias.id.clone(),
+                span: alias.span,
+            }),
+            Some(ty) if !ty.params().is_empty() => {
+                errors.push(ValidationError::WrongTypeArgCount {
+                    type_id: alias.target.clone(),
+                    expected: ty.params().len(),
+                    found: 0,
+                    used_in: alias.id.clone(),
+                    span: alias.span,
+                })
+            }
+            Some(_) => {}
+        }
+        let mut target = alias.target.as_str();
+        for _ in 0..self.types.len() {
+            match self.index.resolve(target) {
+                Some(TypeRef::Alias(next)) if next.id == alias.id => {
+                    let span = alias.span;
+                    errors.push(ValidationError::CyclicAlias { alias: alias.id.clone(), span });
+                    return;
+                }
+                Some(TypeRef::Alias(next)) => target = &next.target,
+                _ => return,
+            }
+        }
+    }
+
+    /// Checks `fields` of `owner`. Names of `inherited` fields (the attributes
+    /// of the type) are reserved but the fields themselves are not checked.
+    /// `params` are the type parameters of the enclosing declaration.
+    fn check_fields(
+        &self,
+        owner: &str,
+        fields: &[Field],
+        inherited: &[Field],
+        params: &[String],
+        errors: &mut Vec<ValidationError>,
+    ) {
+        let inherited_tags = inherited.iter().filter_map(|f| Some((f.tag?, f.span))).collect();
+        let tags = fields.iter().filter_map(|f| Some((f.tag?, f.span)));
+        check_tags(owner, inherited_tags, tags, errors);
+        let mut ids: HashMap<&str, Span> =
+            inherited.iter().map(|f| (f.id.as_str(), f.span)).collect();
+        for f in fields {
+            let field = format!("{}.{}", owner, f.id);
+            match &f.tuple {
+                Some(elements) => self.check_fields(&field, elements, &[], params, errors),
+                None => self.check_type(&f.type_id, &f.args, params, &field, f.span, errors),
+            }
+            let mut arity = Some(&f.arity);
+            while let Some(a) = arity {
+                if let Arity::Map { key, .. } = a {
+                    self.check_type(key, &[], params, &field, f.span, errors);
+                }
+                arity = a.inner();
+            }
+            if let Some(constraint) = &f.constraint {
+                self.check_constraint(constraint, f, &field, params, errors);
+            }
+            if let Some(default) = f.default.as_ref().filter(|d| !self.fits(d, f, params)) {
+                errors.push(ValidationError::InvalidDefault {
+                    default: default.to_string(),
+                    field: field.clone(),
+                    span: f.span,
+                });
+            }
+            match ids.get(f.id.as_str()) {
+                Some(first) => errors.push(ValidationError::DuplicateField {
+                    id: f.id.clone(),
+                    owner: owner.into(),
+                    span: f.span,
+                    first: *first,
+                }),
+                None => {
+                    ids.insert(&f.id, f.span);
+                }
+            }
+        }
+    }
+
+    /// Reports fields of generic types which lead to instantiations with ever
+    /// larger type arguments, directly as in `t<T> = (t<t<T>>? x)` or through
+    /// other generic types. Type parameters are the nodes of a graph with an
+    /// edge to every parameter they are passed to, a growing one if they are
+    /// nested in the argument. A growing edge on a cycle is an error.
+    fn check_instantiations(&self, errors: &mut Vec<ValidationError>) {
+        let mut edges = vec![];
+        for ty in self.types.iter().filter(|t| !t.params().is_empty()) {
+            let mut fields = vec![];
+            match ty {
+                Type::SumType(sty) => {
+                    for c in sty.constructors.iter() {
+                        fields.push((format!("{}.{}", sty.id, c.id), &c.fields));
+                    }
+                    fields.push((sty.id.clone(), &sty.attributes));
+                }
+                Type::ProdType(pty) => {
+                    fields.push((pty.id.clone(), &pty.fields));
+                    fields.push((pty.id.clone(), &pty.attributes));
+                }
+                Type::AliasType(_) | Type::ExternType(_) => {}
+            }
+            for (owner, fields) in fields {
+                self.instantiation_edges(ty, &owner, fields, &mut edges);
+            }
+        }
+        let mut graph: HashMap<_, Vec<_>> = HashMap::new();
+        for e in edges.iter() {
+            graph.entry(e.from).or_default().push(e.to);
+        }
+        let mut reported = HashSet::new();
+        for e in edges.iter().filter(|e| e.growing) {
+            if reachable(&graph, e.to, e.from) && reported.insert(&e.field) {
+                errors.push(ValidationError::InfiniteInstantiation {
+                    type_id: e.to.0.to_string(),
+                    field: e.field.clone(),
+                    span: e.span,
+                });
+            }
+        }
+    }
+
+    fn instantiation_edges(
+        &self,
+        generic: &'a Type,
+        owner: &str,
+        fields: &'a [Field],
+        edges: &mut Vec<Edge<'a>>,
+    ) {
+        for f in fields {
+            let field = format!("{}.{}", owner, f.id);
+            match &f.tuple {
+                Some(elements) => self.instantiation_edges(generic, &field, elements, edges),
+                None => {
+                    let mut uses = vec![(&f.type_id, &f.args)];
+                    while let Some((type_id, args)) = uses.pop() {
+                        uses.extend(args.iter().map(|a| (&a.type_id, &a.args)));
+                        let ty = match self.index.resolve(type_id) {
+                            Some(ty) if ty.params().len() == args.len() => ty,
+                            _ => continue,
+                        };
+                        for (i, param) in generic.params().iter().enumerate() {
+                            for (j, arg) in args.iter().enumerate() {
+                                if mentions(arg, param) {
+                                    edges.push(Edge {
+                                        from: (generic.id(), i),
+                                        to: (ty.id(), j),
+                                        growing: arg.type_id != *param,
+                                        field: field.clone(),
+                                        span: f.span,
+                                    });
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks that ranges restrict `int` and patterns `string` or
+    /// `identifier`, aliases are followed, that ranges are not empty and
+    /// that patterns compile.
+    fn check_constraint(
+        &self,
+        constraint: &Constraint,
+        f: &Field,
+        field: &str,
+        params: &[String],
+        errors: &mut Vec<ValidationError>,
+    ) {
+        let builtin = match self.resolve_alias(&f.type_id) {
+            Some(TypeRef::Builtin(builtin)) if !params.contains(&f.type_id) => builtin,
+            _ => "",
+        };
+        let (fits, expected) = match constraint {
+            Constraint::Range { .. } => (builtin == "int", "`int`"),
+            Constraint::Pattern(_) => {
+                (matches!(builtin, "string" | "identifier"), "`string` or `identifier`")
+            }
+        };
+        if !fits {
+            errors.push(ValidationError::InvalidConstraint {
+                type_id: f.type_id.clone(),
+                expected: expected.to_string(),
+                field: field.to_string(),
+                span: f.span,
+            });
+        }
+        match constraint {
+            Constraint::Range { min: Some(min), max: Some(max) } if min > max => {
+                let field = field.to_string();
+                errors.push(ValidationError::EmptyRange { field, span: f.span });
+            }
+            Constraint::Pattern(pattern) if regex(pattern).is_err() => {
+                errors.push(ValidationError::InvalidPattern {
+                    pattern: pattern.clone(),
+                    field: field.to_string(),
+                    span: f.span,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether `default` fits the type of `field`. `None` fits optional
+    /// fields, integers, booleans and strings fit builtin types of their
+    /// kind and a constructor fits the sum type which declares it without
+    /// fields. Integers must also lie within range constraints and strings
+    /// match patterns. Aliases are followed, type parameters take no
+    /// defaults.
+    fn fits(&self, default: &Literal, field: &Field, params: &[String]) -> bool {
+        if *default == Literal::None {
+            return matches!(field.arity, Arity::Optional(_));
+        }
+        if field.arity != Arity::Required
+            || field.tuple.is_some()
+            || params.contains(&field.type_id)
+        {
+            return false;
+        }
+        if let (Literal::Int(n), Some(Constraint::Range { min, max })) =
+            (default, &field.constraint)
+        {
+            if min.is_some_and(|min| *n < min) || max.is_some_and(|max| *n > max) {
+                return false;
+            }
+        }
+        if let (Literal::String(s), Some(Constraint::Pattern(pattern))) =
+            (default, &field.constraint)
+        {
+            if !matches_pattern(pattern, s) {
+                return false;
+            }
+        }
+        match (default, self.resolve_alias(&field.type_id)) {
+            (Literal::Constructor(id), Some(TypeRef::Sum(sty))) => {
+                sty.constructors.iter().any(|c| c.id == *id && c.fields.is_empty())
+            }
+            (_, Some(TypeRef::Builtin(builtin))) => fits_builtin(default, builtin),
+            _ => false,
+        }
+    }
+
+    /// Resolves `type_id`, following aliases. Cyclic aliases resolve to `None`.
+    fn resolve_alias(&self, type_id: &str) -> Option<TypeRef<'a>> {
+        let mut resolved = self.index.resolve(type_id);
+        for _ in 0..self.types.len() {
+            match resolved {
+                Some(TypeRef::Alias(aty)) => resolved = self.index.resolve(&aty.target),
+                _ => return resolved,
+            }
+        }
+        None
+    }
+
+    /// Checks that `type_id` exists and gets as many type arguments as it has
+    /// parameters, then checks the arguments. Type parameters take none.
+    fn check_type(
+        &self,
+        type_id: &str,
+        args: &[TypeArg],
+        params: &[String],
+        field: &str,
+        span: Span,
+        errors: &mut Vec<ValidationError>,
+    ) {
+        let expected = match self.index.resolve(type_id) {
+            _ if params.iter().any(|p| p == type_id) => Some(0),
+            Some(ty) => Some(ty.params().len()),
+            None => {
+                let (type_id, field) = (type_id.to_string(), field.to_string());
+                errors.push(ValidationError::UndefinedType { type_id, field, span });
+                None
+            }
+        };
+        if let Some(expected) = expected.filter(|n| *n != args.len()) {
+            errors.push(ValidationError::WrongTypeArgCount {
+                type_id: type_id.to_string(),
+                expected,
+                found: args.len(),
+                used_in: field.to_string(),
+                span,
+            });
+        }
+        for arg in args {
+            self.check_type(&arg.type_id, &arg.args, params, field, span, errors);
+        }
+    }
+}
+
+/// `to` parameter is passed an argument which mentions the `from` parameter
+/// in `field`. Parameters are given by type and position.
+struct Edge<'a> {
+    from: (&'a str, usize),
+    to: (&'a str, usize),
+    growing: bool,
+    field: String,
+    span: Span,
+}
+
+/// Whether `param` occurs in `arg` or its type arguments.
+fn mentions(arg: &TypeArg, param: &str) -> bool {
+    arg.type_id == param || arg.args.iter().any(|a| mentions(a, param))
+}
+
+/// Whether `graph` has a path from `from` to `to`.
+fn reachable<'a>(
+    graph: &HashMap<(&'a str, usize), Vec<(&'a str, usize)>>,
+    from: (&'a str, usize),
+    to: (&'a str, usize),
+) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = vec![from];
+    while let Some(node) = stack.pop() {
+        if node == to {
+            return true;
+        }
+        if visited.insert(node) {
+            stack.extend(graph.get(&node).into_iter().flatten().copied());
+        }
+    }
+    false
+}
+
+/// Reports tags of `owner` which are used more than once or which are
+/// already in `seen`.
+fn check_tags(
+    owner: &str,
+    mut seen: HashMap<u32, Span>,
+    tags: impl Iterator<Item = (u32, Span)>,
+    errors: &mut Vec<ValidationError>,
+) {
+    for (tag, span) in tags {
+        match seen.get(&tag) {
+            Some(first) => errors.push(ValidationError::DuplicateTag {
+                tag,
+                owner: owner.into(),
+                span,
+                first: *first,
+            }),
+            None => {
+                seen.insert(tag, span);
+            }
+        }
+    }
+}
+
+/// Whether a literal fits the builtin type `builtin`. CPython's `constant`
+/// takes integers, booleans and strings, `bytes` takes strings.
+fn fits_builtin(literal: &Literal, builtin: &str) -> bool {
+    match literal {
+        Literal::Int(_) => matches!(builtin, "int" | "constant"),
+        Literal::Bool(_) => matches!(builtin, "bool" | "constant"),
+        Literal::String(_) => matches!(builtin, "string" | "identifier" | "bytes" | "constant"),
+        Literal::None | Literal::Constructor(_) => false,
+    }
+}
+
+/// Whether `pattern`, as written in a constraint, matches the whole of
+/// `value`. Patterns which are no valid regular expressions match nothing.
+fn matches_pattern(pattern: &str, value: &str) -> bool {
+    regex(pattern).is_ok_and(|re| re.is_match(value))
+}
+
+/// Compiles the pattern of a constraint so that it matches whole values.
+fn regex(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{})$", pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_schema() {
+        let asdl = r#"
+            stm = Compound(stm s1, stm* s2) | Single(stm, identifier? name)
+                attributes(int lineno)
+            prodType = (stm, string s, {identifier: stm*} named) attributes(int lineno)
+            dict = ((stm, stm?)* items, {string: (int line, int)} lines)
+            name = identifier
+            label = name
+            spanned<T> = (T node, int start)
+            located<T> = Loc(spanned<T> value) | Missing attributes (T? origin)
+            expr = Name(located<identifier> id, spanned<located<expr>>* items)
+            ctx = Load | Store
+            call = (int depth = -1, label l = "main", expr? e = None, ctx c = Load, bool b = false)
+            op = (int(0..255) code = 0, label(/[a-z]+/)? name, int(>=0)* depths, string(/a\/b/) s = "a/b")
+            extern span(rust = "text_size::TextRange")
+            range = (span, spanned<span> s)
+            tagged = A = 1 (1: int a, 2: int b) | B = 2 (1: int a) attributes (3: int line)
+            "#;
+        assert_eq!(Asdl::parse(asdl).unwrap().validate(), Ok(()));
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let asdl = r#"
+            stm = Compound(stm, stm stm) | Single(exp value, int lineno) | Single
+                attributes(int lineno)
+            stm = (str s)
+            expr = Compound
+            arg = (int lineno) attributes(int lineno, col col)
+            keywords = ({name: expr} kw)
+            dict = ((expr, key k, int k)* items)
+            label = lbl
+            a = b
+            b = a
+            c = a
+            pair<K, V> = (K key, V value)
+            generic = (T x, pair<int> p, pair<int, V> q, int<expr> n, pair<int, string<T>> r)
+            lbl2 = pair
+            defaults = (int? a = 0, stm b = Compound, expr c = Name, int* d = None, T e = 1)
+            literals = (int a = "abc", string b = 5, identifier c = true, string(/[a-z]+/) d = "A1")
+            constraints = (stm(0..1) a, int(5..1) b, int(<0) c = 0, int(/a+/) p, string(0..3) q)
+            patterns = (identifier(/(a/) a, bool(/t/) b)
+            extern span(rust = "Span", python = "Span", rust = "TextRange")
+            extern lbl2
+            tagged = A = 1 (1: int a, 1: int b, 2: int c) | B = 1 attributes (2: int line, 2: int col)
+            "#;
+        let errors = Asdl::parse(asdl).unwrap().validate().unwrap_err();
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "Field `stm` is defined more than once in `stm.Compound`",
+                "Undefined type `exp` referenced by field `stm.Single.value`",
+                "Field `lineno` is defined more than once in `stm.Single`",
+                "Constructor `Single` is defined more than once in `stm`",
+                "Type `stm` is defined more than once",
+                "Undefined type `str` referenced by field `stm.s`",
+                "Constructor `Compound` of `expr` is already defined in `stm`",
+                "Field `lineno` is defined more than once in `arg`",
+                "Undefined type `col` referenced by field `arg.col`",
+                "Undefined type `name` referenced by field `keywords.kw`",
+                "Undefined type `key` referenced by field `dict.items.k`",
+                "Field `k` is defined more than once in `dict.items`",
+                "Undefined type `lbl` referenced by alias `label`",
+                "Alias `a` refers to itself",
+                "Alias `b` refers to itself",
+                "Undefined type `T` referenced by field `generic.x`",
+                "Type `pair` takes 2 type arguments, found 1 in `generic.p`",
+                "Undefined type `V` referenced by field `generic.q`",
+                "Type `int` takes 0 type arguments, found 1 in `generic.n`",
+                "Type `string` takes 0 type arguments, found 1 in `generic.r`",
+                "Undefined type `T` referenced by field `generic.r`",
+                "Type `pair` takes 2 type arguments, found 0 in `lbl2`",
+                "Default value `0` does not fit the type of field `defaults.a`",
+                "Default value `Compound` does not fit the type of field `defaults.b`",
+                "Default value `Name` does not fit the type of field `defaults.c`",
+                "Default value `None` does not fit the type of field `defaults.d`",
+                "Undefined type `T` referenced by field `defaults.e`",
+                "Default value `1` does not fit the type of field `defaults.e`",
+                "Default value `\"abc\"` does not fit the type of field `literals.a`",
+                "Default value `5` does not fit the type of field `literals.b`",
+                "Default value `true` does not fit the type of field `literals.c`",
+                "Default value `\"A1\"` does not fit the type of field `literals.d`",
+                "Constraint of field `constraints.a` requires `int`, found `stm`",
+                "Range of field `constraints.b` admits no value",
+                "Default value `0` does not fit the type of field `constraints.c`",
+                "Constraint of field `constraints.p` requires `string` or `identifier`, found `int`",
+                "Constraint of field `constraints.q` requires `int`, found `string`",
+                "Pattern `/(a/` of field `patterns.a` is not a valid regular expression",
+                "Constraint of field `patterns.b` requires `string` or `identifier`, found `bool`",
+                "Language `rust` is mapped more than once in `span`",
+                "Type `lbl2` is defined more than once",
+                "Tag `1` is used more than once in `tagged`",
+                "Tag `1` is used more than once in `tagged.A`",
+                "Tag `2` is used more than once in `tagged.A`",
+                "Tag `2` is used more than once in `tagged`",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_growing_instantiations() {
+        let asdl = r"
+            t<T> = (t<t<T>>? x)
+            u = (t<int> y)
+            a<T> = A(b<list<T>> x) | Empty
+            b<T> = (a<T>? y)
+            list<T> = (T* items)
+            tree<T> = (T value, list<tree<T>> children, (int, tree<T>)* rest)
+            ";
+        let errors = Asdl::parse(asdl).unwrap().validate().unwrap_err();
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "Field `t.x` instantiates `t` with ever larger type arguments",
+                "Field `a.A.x` instantiates `b` with ever larger type arguments",
+            ]
+        );
+    }
+}
@@ -1,11 +1,10 @@
 pub mod model;
 mod model_impl;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::error::Error;
 use std::fmt;
-
-use asdl;
+use std::fs;
 
 use tera::*;
 use heck::{CamelCase, ShoutySnakeCase, SnakeCase, MixedCase};
@@ -15,11 +14,61 @@ pub type Result<T> = std::result::Result<T, AsdlTeraError>;
 #[derive(Debug)]
 pub struct AsdlTeraError {
     details: String,
+    diagnostics: Vec<asdl::Diagnostic>,
 }
 
 impl AsdlTeraError {
     fn new(msg: &str) -> Self {
-        AsdlTeraError { details: msg.to_string() }
+        AsdlTeraError { details: msg.to_string(), diagnostics: vec![] }
+    }
+
+    fn with_diagnostics(msg: &str, diagnostics: Vec<asdl::Diagnostic>) -> Self {
+        AsdlTeraError { details: msg.to_string(), diagnostics }
+    }
+
+    /// Diagnostics of the asdl errors in the schema, if that is what failed.
+    pub fn diagnostics(&self) -> &[asdl::Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Renders the error. Diagnostics in files are shown with the source of
+    /// the file, other ones with `src`.
+    pub fn render(&self, file_name: &str, src: &str, renderer: &asdl::Renderer) -> String {
+        if self.diagnostics.is_empty() {
+            return format!("error: {}\n", self.details);
+        }
+        self.diagnostics
+            .iter()
+            .map(|d| match &d.file {
+                Some(file) => {
+                    let src = fs::read_to_string(file).unwrap_or_default();
+                    renderer.render(&file.display().to_string(), &src, d)
+                }
+                None => renderer.render(file_name, src, d),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Validation errors of a schema loaded from `files`. Secondary labels
+    /// in other files than the primary one are turned into notes.
+    fn from_validation(errors: Vec<asdl::ValidationError>, files: &[PathBuf]) -> Self {
+        let mut err = AsdlTeraError::from(errors);
+        for diagnostic in err.diagnostics.iter_mut() {
+            let file = match &diagnostic.primary {
+                Some(primary) => primary.span.file,
+                None => continue,
+            };
+            diagnostic.file = files.get(file).cloned();
+            let (local, other) = diagnostic.secondary.drain(..).partition(|l| l.span.file == file);
+            diagnostic.secondary = local;
+            for label in other.into_iter() {
+                let path = files.get(label.span.file).map(|f| f.display().to_string());
+                let path = path.unwrap_or_default();
+                diagnostic.notes.push(format!("{} at {}:{}", label.message, path, label.span));
+            }
+        }
+        err
     }
 }
 
@@ -37,24 +86,77 @@ impl Error for AsdlTeraError {
 
 impl From<tera::Error> for AsdlTeraError {
     fn from(err: tera::Error) -> Self {
-        AsdlTeraError::new(err.description())
+        AsdlTeraError::new(&err.to_string())
     }
 }
 
 impl From<asdl::AsdlError> for AsdlTeraError {
     fn from(err: asdl::AsdlError) -> Self {
-        AsdlTeraError::new(err.description())
+        AsdlTeraError::with_diagnostics(&err.to_string(), vec![(&err).into()])
+    }
+}
+
+impl From<Vec<asdl::AsdlError>> for AsdlTeraError {
+    fn from(errors: Vec<asdl::AsdlError>) -> Self {
+        let msgs: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        let diagnostics = errors.iter().map(Into::into).collect();
+        AsdlTeraError::with_diagnostics(&msgs.join("\n"), diagnostics)
+    }
+}
+
+impl From<Vec<asdl::ValidationError>> for AsdlTeraError {
+    fn from(errors: Vec<asdl::ValidationError>) -> Self {
+        let msgs: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        let diagnostics = errors.iter().map(Into::into).collect();
+        AsdlTeraError::with_diagnostics(&msgs.join("\n"), diagnostics)
     }
 }
 
 impl From<std::io::Error> for AsdlTeraError {
     fn from(err: std::io::Error) -> Self {
-        AsdlTeraError::new(err.description())
+        AsdlTeraError::new(&err.to_string())
+    }
+}
+
+/// Reformats an asdl schema in canonical style.
+pub fn format(asdl: &str) -> Result<String> {
+    format_with(asdl, &asdl::PrintOptions::default())
+}
+
+/// Reformats an asdl schema with the given formatting options.
+pub fn format_with(asdl: &str, options: &asdl::PrintOptions) -> Result<String> {
+    let (model, errors) = asdl::Asdl::parse_partial(asdl);
+    if !errors.is_empty() {
+        return Err(errors.into());
     }
+    Ok(model.to_asdl_string_with(options))
+}
+
+/// Options of the model passed to templates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Options {
+    /// Replaces generic types with one concrete type per instantiation, for
+    /// target languages without generics. See `asdl::Asdl::monomorphize`.
+    pub monomorphize: bool,
+    /// Assigns tags to constructors and fields without one and records them
+    /// in the lock file next to the schema. See `asdl::TagLock`.
+    pub lock: bool,
+}
+
+/// Generates code from the schema in `asdl_file` and the files it imports.
+pub fn generate<A: AsRef<Path>, P: AsRef<Path>>(
+    asdl_file: A,
+    templates: &Vec<P>,
+) -> Result<String> {
+    generate_with_options(asdl_file, templates, &Options::default())
 }
 
-pub fn generate<P: AsRef<Path>>(asdl: &str, templates: &Vec<P>) -> Result<String> {
-    let model = model::Asdl::parse(asdl)?;
+pub fn generate_with_options<A: AsRef<Path>, P: AsRef<Path>>(
+    asdl_file: A,
+    templates: &Vec<P>,
+    options: &Options,
+) -> Result<String> {
+    let model = model::Asdl::load_with_options(asdl_file, options)?;
     let mut tera = Tera::default();
     tera.register_filter("camel", |arg, _| Ok(arg.as_str().unwrap().to_camel_case().into()));
     tera.register_filter("snake", |arg, _| Ok(arg.as_str().unwrap().to_snake_case().into()));
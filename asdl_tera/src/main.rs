@@ -1,14 +1,18 @@
 use std::fs;
+use std::io::{self, IsTerminal};
 use std::path::Path;
+use std::process;
 
-use clap::{App, Arg};
-use asdl_tera::{generate, Result};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use asdl::{BarAlignment, CommentPlacement, PrintOptions};
+use asdl_tera::{format_with, generate_with_options, Options, Result};
 
 fn main() -> Result<()> {
     let matches = App::new("Asdl generator")
         .version("0.1.0")
         .author("Sergey Parilin <parilinsa@gmail.com>")
         .about("Parses asdl notation and generates source files according template.")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("asdl")
                 .short("i")
@@ -33,12 +37,140 @@ fn main() -> Result<()> {
                 .value_name("OUT FILE")
                 .help("Output file"),
         )
+        .arg(
+            Arg::with_name("monomorphize")
+                .long("monomorphize")
+                .help("Replaces generic types with one type per instantiation"),
+        )
+        .arg(
+            Arg::with_name("lock")
+                .long("lock")
+                .help("Assigns missing tags and records them in <ASDL FILE>.lock"),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .value_name("WHEN")
+                .help("Coloring of error messages")
+                .possible_values(&["auto", "always", "never"])
+                .default_value("auto")
+                .global(true),
+        )
+        .subcommand(
+            SubCommand::with_name("fmt")
+                .about("Reformats asdl files in place")
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .help("Only checks that the files are formatted"),
+                )
+                .arg(
+                    Arg::with_name("bars")
+                        .long("bars")
+                        .value_name("ALIGN")
+                        .help("Aligns '|' under the '=' or indents it by a number of spaces")
+                        .validator(|v| if v == "equals" { Ok(()) } else { number(v) })
+                        .default_value("equals"),
+                )
+                .arg(
+                    Arg::with_name("width")
+                        .long("width")
+                        .value_name("COLUMNS")
+                        .help("Breaks lines longer than this")
+                        .validator(number)
+                        .default_value("100"),
+                )
+                .arg(
+                    Arg::with_name("comments")
+                        .long("comments")
+                        .value_name("PLACEMENT")
+                        .help("Keeps comments above their items or drops them")
+                        .possible_values(&["above", "omit"])
+                        .default_value("above"),
+                )
+                .arg(
+                    Arg::with_name("indent")
+                        .long("indent")
+                        .value_name("SPACES")
+                        .help("Indentation of types inside a module")
+                        .validator(number)
+                        .default_value("4"),
+                )
+                .arg(Arg::with_name("files").value_name("ASDL FILE").multiple(true).required(true)),
+        )
         .get_matches();
-    let template_files = matches.values_of("template").unwrap().map(|t| Path::new(t)).collect();
+    if let Some(matches) = matches.subcommand_matches("fmt") {
+        return fmt(matches);
+    }
+    let template_files = matches.values_of("template").unwrap().map(Path::new).collect();
     let asdl_file = matches.value_of("asdl").unwrap();
-    let asdl = fs::read_to_string(asdl_file).unwrap();
     let output_file = matches.value_of("output").unwrap();
-    let res = generate(&asdl, &template_files)?;
+    let options = Options {
+        monomorphize: matches.is_present("monomorphize"),
+        lock: matches.is_present("lock"),
+    };
+    let res = match generate_with_options(asdl_file, &template_files, &options) {
+        Ok(res) => res,
+        Err(err) => {
+            eprint!("{}", err.render(asdl_file, "", &renderer(&matches)));
+            process::exit(1);
+        }
+    };
     fs::write(Path::new(output_file), res)?;
     Ok(())
 }
+
+fn fmt(matches: &ArgMatches) -> Result<()> {
+    let check = matches.is_present("check");
+    let options = print_options(matches);
+    let mut failed = false;
+    for file in matches.values_of("files").unwrap() {
+        let asdl = fs::read_to_string(file)?;
+        match format_with(&asdl, &options) {
+            Ok(formatted) if formatted == asdl => (),
+            Ok(_) if check => {
+                eprintln!("{} is not formatted", file);
+                failed = true;
+            }
+            Ok(formatted) => fs::write(file, formatted)?,
+            Err(err) => {
+                eprint!("{}", err.render(file, &asdl, &renderer(matches)));
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Formatting options of the `fmt` subcommand. Numbers are checked by the
+/// argument validators.
+fn print_options(matches: &ArgMatches) -> PrintOptions {
+    let count = |name| matches.value_of(name).unwrap().parse().unwrap();
+    PrintOptions {
+        bar_alignment: match matches.value_of("bars") {
+            Some("equals") => BarAlignment::Equals,
+            _ => BarAlignment::Indent(count("bars")),
+        },
+        line_width: count("width"),
+        comments: match matches.value_of("comments") {
+            Some("omit") => CommentPlacement::Omit,
+            _ => CommentPlacement::Above,
+        },
+        indent: count("indent"),
+    }
+}
+
+fn number(value: String) -> std::result::Result<(), String> {
+    value.parse::<usize>().map(|_| ()).map_err(|_| format!("{} is not a number", value))
+}
+
+fn renderer(matches: &ArgMatches) -> asdl::Renderer {
+    match matches.value_of("color") {
+        Some("always") => asdl::Renderer::colored(),
+        Some("auto") if io::stderr().is_terminal() => asdl::Renderer::colored(),
+        _ => asdl::Renderer::plain(),
+    }
+}
@@ -1,27 +1,82 @@
 use std::collections::HashMap;
+use std::path::Path;
+
 use serde::Serialize;
-use asdl;
-use super::Result;
+use tera::Value;
+use super::{AsdlTeraError, Options, Result};
 
 #[derive(Serialize, Debug)]
 pub struct Asdl {
+    pub module: Option<Module>,
     pub types: HashMap<String, Type>,
     pub prod_types: Vec<String>,
     pub sum_types: Vec<String>,
+    pub alias_types: Vec<String>,
+    pub extern_types: Vec<String>,
     pub comments: Vec<String>,
+    pub builtins: Vec<String>,
 }
 
 impl Asdl {
     pub fn parse(asdl: &str) -> Result<Asdl> {
-        Ok(Asdl::new(asdl::Asdl::parse(asdl)?))
+        let (asdl, errors) = asdl::Asdl::parse_partial(asdl);
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+        asdl.validate()?;
+        Ok(Asdl::new(asdl))
+    }
+
+    /// Loads the schema in `path` together with the files it imports.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Asdl> {
+        Asdl::load_with_options(path, &Options::default())
+    }
+
+    pub fn load_with_options<P: AsRef<Path>>(path: P, options: &Options) -> Result<Asdl> {
+        let (mut asdl, errors) = asdl::Asdl::load_partial(&path);
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+        if let Err(errors) = asdl.validate() {
+            return Err(AsdlTeraError::from_validation(errors, &asdl.files));
+        }
+        if options.lock {
+            let lock_path = asdl::TagLock::path(&path);
+            let lock = asdl::TagLock::load(&lock_path)?;
+            let updated = match asdl.assign_tags(&lock) {
+                Ok(updated) => updated,
+                Err(errors) => return Err(AsdlTeraError::from_validation(errors, &asdl.files)),
+            };
+            if let Err(errors) = asdl.validate() {
+                return Err(AsdlTeraError::from_validation(errors, &asdl.files));
+            }
+            if updated != lock {
+                updated.save(&lock_path)?;
+            }
+        }
+        if options.monomorphize {
+            return match asdl.monomorphize() {
+                Ok(mono) => Ok(Asdl::new(mono)),
+                Err(errors) => Err(AsdlTeraError::from_validation(errors, &asdl.files)),
+            };
+        }
+        Ok(Asdl::new(asdl))
     }
 }
 
+#[derive(Serialize, Debug)]
+pub struct Module {
+    pub id: String,
+    pub version: Option<String>,
+}
+
 #[derive(Serialize, Debug)]
 #[serde(untagged)]
 pub enum Type {
     SumType(SumType),
     ProdType(ProdType),
+    AliasType(AliasType),
+    ExternType(ExternType),
 }
 
 impl From<SumType> for Type {
@@ -34,36 +89,174 @@ impl From<ProdType> for Type {
         Type::ProdType(n)
     }
 }
+impl From<AliasType> for Type {
+    fn from(n: AliasType) -> Type {
+        Type::AliasType(n)
+    }
+}
+impl From<ExternType> for Type {
+    fn from(n: ExternType) -> Type {
+        Type::ExternType(n)
+    }
+}
 
 #[derive(Serialize, Debug)]
 pub struct SumType {
     pub id: String,
+    /// Type parameters of a generic type, see also `is_param` of fields.
+    pub params: Vec<String>,
+    pub is_generic: bool,
     pub constructors: Vec<Constructor>,
     pub attributes: Vec<Field>,
+    /// Annotation values by name, `true` for annotations without a value.
+    pub annotations: HashMap<String, Value>,
     pub is_prod_type: bool, //always false
+    pub is_alias: bool,     //always false
+    pub is_extern: bool,    //always false
     pub comments: Vec<String>,
+    pub span: Span,
+    /// File the type was loaded from.
+    pub file: Option<String>,
 }
 
 #[derive(Serialize, Debug)]
 pub struct Constructor {
     pub id: String,
+    /// Explicit discriminant, `Compound = 3`.
+    pub tag: Option<u32>,
     pub fields: Vec<Field>,
+    pub annotations: HashMap<String, Value>,
     pub comments: Vec<String>,
+    pub span: Span,
 }
 
 #[derive(Serialize, Debug)]
 pub struct ProdType {
     pub id: String,
+    pub params: Vec<String>,
+    pub is_generic: bool,
     pub fields: Vec<Field>,
+    pub attributes: Vec<Field>,
+    pub annotations: HashMap<String, Value>,
     pub is_prod_type: bool, //always true
+    pub is_alias: bool,     //always false
+    pub is_extern: bool,    //always false
+    pub comments: Vec<String>,
+    pub span: Span,
+    /// File the type was loaded from.
+    pub file: Option<String>,
+}
+
+/// `name = target`, templates usually render it as a newtype.
+#[derive(Serialize, Debug)]
+pub struct AliasType {
+    pub id: String,
+    pub target: String,
+    pub is_target_builtin: bool,
+    pub annotations: HashMap<String, Value>,
+    pub is_prod_type: bool, //always false
+    pub is_alias: bool,     //always true
+    pub is_extern: bool,    //always false
     pub comments: Vec<String>,
+    pub span: Span,
+    /// File the type was loaded from.
+    pub file: Option<String>,
+}
+
+/// `extern span`, a type the generated code imports. `mappings` hold its
+/// names by target language, templates fall back to the id for languages
+/// without one.
+#[derive(Serialize, Debug)]
+pub struct ExternType {
+    pub id: String,
+    pub mappings: HashMap<String, String>,
+    pub annotations: HashMap<String, Value>,
+    pub is_prod_type: bool, //always false
+    pub is_alias: bool,     //always false
+    pub is_extern: bool,    //always true
+    pub comments: Vec<String>,
+    pub span: Span,
+    /// File the type was loaded from.
+    pub file: Option<String>,
 }
 
 #[derive(Serialize, Debug)]
 pub struct Field {
     pub id: String,
+    /// Explicit field number, `1: stm body`.
+    pub tag: Option<u32>,
+    /// Empty for inline tuple types such as `(expr, expr)`, `tuple` holds
+    /// their elements. Templates can render them as tuples or generate a
+    /// struct named after the field.
     pub type_id: String,
+    pub is_builtin: bool,
+    pub is_tuple: bool,
+    pub tuple: Vec<Field>,
+    /// Set if the type is a parameter of the enclosing generic type.
+    pub is_param: bool,
+    /// Kind of the referenced type: `builtin`, `sum`, `prod`, `alias` or
+    /// `extern`. Null for tuples and type parameters. Templates find the
+    /// type itself in `types` by `type_id`.
+    pub kind: Option<String>,
+    /// Type arguments of a generic type such as `spanned<expr>`.
+    pub args: Vec<TypeArg>,
+    /// The flags describe the outermost modifier, `is_repeated` is also set
+    /// for `+`. `is_nested` is set if the values themselves have modifiers,
+    /// as in `expr?*`.
     pub is_required: bool,
     pub is_optional: bool,
     pub is_repeated: bool,
+    pub is_non_empty: bool,
+    pub is_nested: bool,
+    /// Set for `{key: value}` fields, `key_type_id` is the key type of the
+    /// outermost map.
+    pub is_map: bool,
+    pub key_type_id: Option<String>,
+    pub is_key_builtin: bool,
+    /// Modifiers outermost first: `optional`, `repeated`, `non_empty` or `map`.
+    pub arity: Vec<String>,
+    pub constraint: Option<Constraint>,
+    pub default: Option<DefaultValue>,
+    pub annotations: HashMap<String, Value>,
+    pub comments: Vec<String>,
+    pub span: Span,
+}
+
+/// Restriction on the values of a builtin type: an inclusive range, either
+/// bound may be missing, or a regular expression with `pattern` set.
+#[derive(Serialize, Debug)]
+pub struct Constraint {
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub pattern: Option<String>,
+}
+
+/// Default value of a field. `kind` is `int`, `bool`, `string`, `none` or
+/// `constructor`, `value` is null for `none` and the constructor id for
+/// `constructor`.
+#[derive(Serialize, Debug)]
+pub struct DefaultValue {
+    pub kind: String,
+    pub value: Value,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TypeArg {
+    pub type_id: String,
+    pub is_builtin: bool,
+    pub is_param: bool,
+    /// Kind of the referenced type, as in `Field`.
+    pub kind: Option<String>,
+    pub args: Vec<TypeArg>,
+}
+
+/// Source location of a schema element. Lines and columns are 1-based.
+#[derive(Serialize, Debug)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
 }
@@ -1,99 +1,311 @@
+use std::collections::HashMap;
+
+use tera::Value;
+
 use crate::model::*;
-use asdl;
 
 impl Asdl {
     pub(crate) fn new(model: asdl::Asdl) -> Self {
         let mut prod_types = Vec::new();
         let mut sum_types = Vec::new();
+        let mut alias_types = Vec::new();
+        let mut extern_types = Vec::new();
         for ty in model.types.iter() {
             match ty {
                 asdl::Type::SumType(sty) => sum_types.push(sty.id.clone()),
                 asdl::Type::ProdType(pty) => prod_types.push(pty.id.clone()),
+                asdl::Type::AliasType(aty) => alias_types.push(aty.id.clone()),
+                asdl::Type::ExternType(ety) => extern_types.push(ety.id.clone()),
             }
         }
-        let types = model.types.into_iter().map(ty).map(|t| (t.id(), t)).collect();
-        let comments = model.comments;
-        Asdl { types, prod_types, sum_types, comments }
+        let index = model.index();
+        let kinds: Kinds = model
+            .builtins
+            .iter()
+            .chain(model.types.iter().map(asdl::Type::id))
+            .filter_map(|id| index.resolve(id).map(|t| (id.to_string(), t.kind())))
+            .collect();
+        let files: Vec<_> =
+            model.types.iter().map(|t| model.file(t).map(|f| f.display().to_string())).collect();
+        let types = model
+            .types
+            .into_iter()
+            .zip(files)
+            .map(|(t, file)| ty(t, file, &kinds))
+            .map(|t| (t.id(), t))
+            .collect();
+        let module = model.module.map(module);
+        let comments = comments(model.comments);
+        let mut builtins: Vec<String> =
+            kinds.into_iter().filter(|(_, kind)| *kind == "builtin").map(|(id, _)| id).collect();
+        builtins.sort();
+        Asdl { module, types, prod_types, sum_types, alias_types, extern_types, comments, builtins }
+    }
+}
+
+impl Module {
+    fn new(id: String, version: Option<String>) -> Self {
+        Module { id, version }
     }
 }
 
+fn module(m: asdl::Module) -> Module {
+    Module::new(m.id, m.version)
+}
+
 impl Type {
     fn id(&self) -> String {
         match self {
             Type::SumType(sty) => sty.id.clone(),
             Type::ProdType(pty) => pty.id.clone(),
+            Type::AliasType(aty) => aty.id.clone(),
+            Type::ExternType(ety) => ety.id.clone(),
         }
     }
 }
 
-fn ty(ty: asdl::Type) -> Type {
+fn ty(ty: asdl::Type, file: Option<String>, kinds: &Kinds) -> Type {
     match ty {
-        asdl::Type::SumType(sty) => sum_type(sty).into(),
-        asdl::Type::ProdType(pty) => prod_type(pty).into(),
+        asdl::Type::SumType(sty) => sum_type(sty, file, kinds).into(),
+        asdl::Type::ProdType(pty) => prod_type(pty, file, kinds).into(),
+        asdl::Type::AliasType(aty) => alias_type(aty, file, kinds).into(),
+        asdl::Type::ExternType(ety) => extern_type(ety, file).into(),
+    }
+}
+
+fn sum_type(ty: asdl::SumType, file: Option<String>, kinds: &Kinds) -> SumType {
+    let params = &ty.params;
+    SumType {
+        constructors: ty.constructors.into_iter().map(|c| constr(c, kinds, params)).collect(),
+        attributes: fields(ty.attributes, kinds, params),
+        is_generic: !ty.params.is_empty(),
+        id: ty.id,
+        params: ty.params,
+        annotations: annotations(ty.annotations),
+        is_prod_type: false,
+        is_alias: false,
+        is_extern: false,
+        comments: comments(ty.comments),
+        span: ty.span.into(),
+        file,
     }
 }
 
-impl SumType {
+impl Constructor {
     fn new(
         id: String,
-        constructors: Vec<Constructor>,
-        attributes: Vec<Field>,
+        tag: Option<u32>,
+        fields: Vec<Field>,
+        annotations: HashMap<String, Value>,
         comments: Vec<String>,
+        span: Span,
     ) -> Self {
-        SumType { id, constructors, attributes, is_prod_type: false, comments }
+        Constructor { id, tag, fields, annotations, comments, span }
     }
 }
 
-fn sum_type(ty: asdl::SumType) -> SumType {
-    let id = ty.id;
-    let constructors = ty.constructors.into_iter().map(constr).collect();
-    let attributes = fields(ty.attributes);
-    let comments = ty.comments;
-    SumType::new(id, constructors, attributes, comments)
+fn constr(c: asdl::Constructor, kinds: &Kinds, params: &[String]) -> Constructor {
+    let fields = fields(c.fields, kinds, params);
+    let annotations = annotations(c.annotations);
+    Constructor::new(c.id, c.tag, fields, annotations, comments(c.comments), c.span.into())
 }
 
-impl Constructor {
-    fn new(id: String, fields: Vec<Field>, comments: Vec<String>) -> Self {
-        Constructor { id, fields, comments }
+fn prod_type(ty: asdl::ProdType, file: Option<String>, kinds: &Kinds) -> ProdType {
+    let params = &ty.params;
+    ProdType {
+        fields: fields(ty.fields, kinds, params),
+        attributes: fields(ty.attributes, kinds, params),
+        is_generic: !ty.params.is_empty(),
+        id: ty.id,
+        params: ty.params,
+        annotations: annotations(ty.annotations),
+        is_prod_type: true,
+        is_alias: false,
+        is_extern: false,
+        comments: comments(ty.comments),
+        span: ty.span.into(),
+        file,
+    }
+}
+
+impl AliasType {
+    fn new(
+        id: String,
+        target: String,
+        is_target_builtin: bool,
+        annotations: HashMap<String, Value>,
+        comments: Vec<String>,
+        span: Span,
+        file: Option<String>,
+    ) -> Self {
+        let is_prod_type = false;
+        let is_alias = true;
+        let is_extern = false;
+        AliasType {
+            id,
+            target,
+            is_target_builtin,
+            annotations,
+            is_prod_type,
+            is_alias,
+            is_extern,
+            comments,
+            span,
+            file,
+        }
     }
 }
 
-fn constr(c: asdl::Constructor) -> Constructor {
-    Constructor::new(c.id, fields(c.fields), c.comments)
+fn alias_type(ty: asdl::AliasType, file: Option<String>, kinds: &Kinds) -> AliasType {
+    let is_target_builtin = is_builtin(&ty.target, kinds);
+    let annotations = annotations(ty.annotations);
+    let comments = comments(ty.comments);
+    let span = ty.span.into();
+    AliasType::new(ty.id, ty.target, is_target_builtin, annotations, comments, span, file)
 }
 
-impl ProdType {
-    fn new(id: String, fields: Vec<Field>, comments: Vec<String>) -> Self {
-        ProdType { id, fields, is_prod_type: true, comments }
+fn extern_type(ty: asdl::ExternType, file: Option<String>) -> ExternType {
+    ExternType {
+        id: ty.id,
+        mappings: ty.mappings.into_iter().map(|m| (m.language, m.target)).collect(),
+        annotations: annotations(ty.annotations),
+        is_prod_type: false,
+        is_alias: false,
+        is_extern: true,
+        comments: comments(ty.comments),
+        span: ty.span.into(),
+        file,
     }
 }
 
-fn prod_type(ty: asdl::ProdType) -> ProdType {
-    ProdType::new(ty.id, fields(ty.fields), ty.comments)
+/// Kinds of the builtin and declared types by name, see `asdl::TypeRef::kind`.
+type Kinds = HashMap<String, &'static str>;
+
+fn is_builtin(type_id: &str, kinds: &Kinds) -> bool {
+    kinds.get(type_id) == Some(&"builtin")
+}
+
+/// Kind of the type `type_id` refers to. `None` for type parameters, tuples
+/// and undefined types.
+fn kind(type_id: &str, kinds: &Kinds, params: &[String]) -> Option<String> {
+    if params.iter().any(|p| p == type_id) {
+        return None;
+    }
+    kinds.get(type_id).map(|kind| kind.to_string())
+}
+
+fn fields(fields: Vec<asdl::Field>, kinds: &Kinds, params: &[String]) -> Vec<Field> {
+    fields.into_iter().map(|f| field(f, kinds, params)).collect()
 }
 
-impl Field {
-    fn required(id: String, type_id: String) -> Self {
-        Field { id, type_id, is_required: true, is_optional: false, is_repeated: false }
+/// `params` are the type parameters of the declaration the field is in.
+fn field(f: asdl::Field, kinds: &Kinds, params: &[String]) -> Field {
+    let arity = f.arity;
+    let key_type_id = map_key(&arity).map(str::to_string);
+    Field {
+        id: f.id,
+        tag: f.tag,
+        is_builtin: is_builtin(&f.type_id, kinds),
+        is_param: params.contains(&f.type_id),
+        kind: kind(&f.type_id, kinds, params),
+        type_id: f.type_id,
+        is_tuple: f.tuple.is_some(),
+        args: type_args(f.args, kinds, params),
+        tuple: fields(f.tuple.unwrap_or_default(), kinds, params),
+        is_required: arity == asdl::Arity::Required,
+        is_optional: matches!(arity, asdl::Arity::Optional(_)),
+        is_repeated: arity.is_sequence(),
+        is_non_empty: matches!(arity, asdl::Arity::NonEmpty(_)),
+        is_nested: arity.inner().is_some_and(|a| *a != asdl::Arity::Required),
+        is_map: matches!(arity, asdl::Arity::Map { .. }),
+        is_key_builtin: key_type_id.as_ref().is_some_and(|key| is_builtin(key, kinds)),
+        key_type_id,
+        arity: modifiers(&arity),
+        constraint: f.constraint.map(constraint),
+        default: f.default.map(default_value),
+        annotations: annotations(f.annotations),
+        comments: comments(f.comments),
+        span: f.span.into(),
+    }
+}
+
+fn constraint(constraint: asdl::Constraint) -> Constraint {
+    match constraint {
+        asdl::Constraint::Range { min, max } => Constraint { min, max, pattern: None },
+        asdl::Constraint::Pattern(regex) => {
+            Constraint { min: None, max: None, pattern: Some(regex) }
+        }
     }
+}
+
+fn default_value(literal: asdl::Literal) -> DefaultValue {
+    let (kind, value) = match literal {
+        asdl::Literal::Int(n) => ("int", Value::from(n)),
+        asdl::Literal::Bool(b) => ("bool", Value::Bool(b)),
+        asdl::Literal::String(s) => ("string", Value::String(s)),
+        asdl::Literal::None => ("none", Value::Null),
+        asdl::Literal::Constructor(id) => ("constructor", Value::String(id)),
+    };
+    DefaultValue { kind: kind.to_string(), value }
+}
+
+fn type_args(args: Vec<asdl::TypeArg>, kinds: &Kinds, params: &[String]) -> Vec<TypeArg> {
+    args.into_iter()
+        .map(|a| TypeArg {
+            is_builtin: is_builtin(&a.type_id, kinds),
+            is_param: params.contains(&a.type_id),
+            kind: kind(&a.type_id, kinds, params),
+            args: type_args(a.args, kinds, params),
+            type_id: a.type_id,
+        })
+        .collect()
+}
 
-    fn optional(id: String, type_id: String) -> Self {
-        Field { id, type_id, is_required: false, is_optional: true, is_repeated: false }
+fn map_key(mut arity: &asdl::Arity) -> Option<&str> {
+    loop {
+        match arity {
+            asdl::Arity::Map { key, .. } => return Some(key),
+            _ => arity = arity.inner()?,
+        }
     }
+}
 
-    fn repeated(id: String, type_id: String) -> Self {
-        Field { id, type_id, is_required: false, is_optional: false, is_repeated: true }
+fn modifiers(mut arity: &asdl::Arity) -> Vec<String> {
+    let mut modifiers = vec![];
+    while let Some(inner) = arity.inner() {
+        let modifier = match arity {
+            asdl::Arity::Optional(_) => "optional",
+            asdl::Arity::Repeated(_) => "repeated",
+            asdl::Arity::Map { .. } => "map",
+            _ => "non_empty",
+        };
+        modifiers.push(modifier.to_string());
+        arity = inner;
     }
+    modifiers
 }
 
-fn fields(fields: Vec<asdl::Field>) -> Vec<Field> {
-    fields.into_iter().map(field).collect()
+fn annotations(annotations: Vec<asdl::Annotation>) -> HashMap<String, Value> {
+    annotations
+        .into_iter()
+        .map(|a| (a.name, a.value.map_or(Value::Bool(true), Value::String)))
+        .collect()
 }
 
-fn field(f: asdl::Field) -> Field {
-    match f.arity {
-        asdl::Arity::Required => Field::required(f.id, f.type_id),
-        asdl::Arity::Optional => Field::optional(f.id, f.type_id),
-        asdl::Arity::Repeated => Field::repeated(f.id, f.type_id),
+/// Texts of doc comments. Other comments are not meant for generated code.
+fn comments(comments: Vec<asdl::Comment>) -> Vec<String> {
+    comments.into_iter().filter(asdl::Comment::is_doc).map(|c| c.text).collect()
+}
+
+impl From<asdl::Span> for Span {
+    fn from(span: asdl::Span) -> Span {
+        Span {
+            start: span.start.offset,
+            end: span.end.offset,
+            line: span.start.line,
+            column: span.start.column,
+            end_line: span.end.line,
+            end_column: span.end.column,
+        }
     }
 }
@@ -10,7 +10,7 @@ type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
 pub fn run_rust_fmt_check() -> Result<()> {
     match Command::new("rustup")
-        .args(&["run", TOOLCHAIN, "--", "cargo", "fmt", "--version"])
+        .args(["run", TOOLCHAIN, "--", "cargo", "fmt", "--version"])
         .stderr(Stdio::null())
         .stdout(Stdio::null())
         .status()
@@ -1,6 +1,5 @@
 use std::{fs, path::Path};
 
-use term;
 use difference::{Difference, Changeset};
 use asdl_tests::{project_root, run_rust_fmt_check};
 
@@ -24,10 +23,9 @@ fn check_syntax_is_fresh() {
     let macros_file = Path::new(MACROS);
     let asdl_file = project_root().join(ASDL);
     let generated_file = project_root().join(GENERATED);
-    let asdl = fs::read_to_string(asdl_file).unwrap();
     let current_content = fs::read_to_string(generated_file).unwrap();
     std::env::set_current_dir(project_root()).unwrap();
-    let new_content = generate(&asdl, &vec![macros_file, template_file]).unwrap();
+    let new_content = generate(&asdl_file, &vec![macros_file, template_file]).unwrap();
     let changeset = Changeset::new(&new_content, &current_content, "\n");
     if changeset.diffs.len() == 1 && changeset.diffs[0] == Difference::Same(new_content) {
         return;
@@ -52,8 +50,7 @@ fn check_syntax_is_fresh() {
     }
     t.reset().unwrap();
     t.flush().unwrap();
-    assert!(
-        false,
+    panic!(
         "Generated syntax is out of date. See the diff above. Please generate new one with 'cargo gen-syntax' command."
     );
 }